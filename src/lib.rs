@@ -1,110 +1,894 @@
+mod macros;
+pub mod model;
+pub mod scenario;
+
 pub mod online_shop {
     use std::marker::PhantomData;
 
+    use session::Session;
+
+    mod any_customer;
+    mod cart;
+    mod catalogue;
+    mod clock;
+    mod dispatch;
+    mod events;
+    mod graph;
+    mod inventory;
+    mod journal;
+    mod money;
+    mod non_empty;
+    mod order;
+    mod payments;
+    mod promotions;
+    mod returns;
+    mod session;
+    mod shipping;
+    mod shop;
+    mod tax;
+
+    pub use any_customer::AnyCustomer;
+    pub use cart::{display_cart, LineItem};
+    pub use catalogue::{Catalogue, Product, Sku};
+    pub use clock::{Clock, FixedClock, SystemClock, Timestamp};
+    pub use dispatch::{Action, ApplyError, InvalidTransition, ParseActionError, Rejection};
+    pub use events::{EventSink, NoopSink, StdoutSink, TransitionEvent, VecSink};
+    pub use graph::{graph, order_graph, Edge, Graph, Node};
+    pub use inventory::{InMemoryInventory, Inventory, OutOfStock, ReservationId, Unavailable};
+    pub use journal::{replay, replay_in, replay_with, CustomerEvent, Journal, ReplayError};
+    pub use money::{
+        Currency, Eur, ExchangeRate, ExchangeRates, FixedRates, Gbp, Jpy, Money, MoneyError,
+        ParseMoneyError, Rounding, Usd,
+    };
+    pub use non_empty::NonEmpty;
+    pub use order::{
+        Cancelled, Delivered, Order, OrderId, OrderState, Packed, Paid, Refunded, ReturnReceived,
+        ReturnRejected, ReturnRequested, Shipped,
+    };
+    pub use payments::{
+        Authorization, MockGateway, MockResponse, Operation, ParsePaymentMethodError, Payment,
+        PaymentError, PaymentGateway, PaymentMethod, Refund,
+    };
+    pub use promotions::{Coupon, CouponError, Discount, PromotionEngine};
+    pub use returns::{RefundLine, ReturnError, ReturnLine, ReturnPolicy, ReturnRequest};
+    pub use session::AbandonedSession;
+    pub use shipping::{ParseShippingOptionError, ShippingOption};
+    pub use shop::{CustomerId, Shop};
+    pub use tax::{
+        ParseTaxRateError, Pricing, Region, TaxBreakdown, TaxCategory, TaxConfigError, TaxError,
+        TaxLine, TaxPolicy, TaxRate, TaxRule, TaxTable,
+    };
+
+    // Everything in the shop is priced in this currency, so e.g. a
+    // `Product<Usd>` can't be put in a cart at all. Prices in other currencies
+    // have to be converted with `Money::convert()` first.
+    pub type StoreCurrency = Eur;
+
     // The different states the customer can be in throughout the shopping flow.
     // We can model a "Left" state if we want, but we don't have to.
     pub struct Browsing;
     pub struct Shopping;
-    pub struct Checkout;
+    // Checking out goes through a step for every part of the checkout form, so
+    // e.g. `Customer<Checkout<NeedsShipping>>` has said where to ship to, but
+    // not yet how. Only `Customer<Checkout<ReadyToPay>>` can pay.
+    pub struct Checkout<S: CheckoutStep>(PhantomData<S>);
+
+    // The steps of checking out, in the order they're gone through.
+    pub struct NeedsAddress;
+    pub struct NeedsShipping;
+    pub struct NeedsPayment;
+    pub struct ReadyToPay;
+
+    // The supertrait of `CustomerState` lives in a private module, so nothing
+    // outside this crate can implement it (and thus `CustomerState`) for their
+    // own types. `Customer<String>` and friends can't even be named.
+    //
+    // It also picks how each state stores the cart. A customer only ever
+    // browses with an empty cart, and only ever shops or checks out with a
+    // non-empty one, so that's what the types say. The same goes for what's
+    // been filled in of the checkout form, which is nothing outside of
+    // checkout.
+    mod sealed {
+        use super::{CheckoutStep, LineItem, NonEmpty, PaymentMethod, ShippingOption};
+
+        pub trait Sealed {
+            type Cart;
+            type Form;
+            const AT_CHECKOUT: bool;
+
+            fn items(cart: &Self::Cart) -> &[LineItem];
+        }
+
+        impl Sealed for super::Browsing {
+            type Cart = ();
+            type Form = ();
+            const AT_CHECKOUT: bool = false;
+
+            fn items(_cart: &()) -> &[LineItem] {
+                &[]
+            }
+        }
+
+        impl Sealed for super::Shopping {
+            type Cart = NonEmpty<LineItem>;
+            type Form = ();
+            const AT_CHECKOUT: bool = false;
+
+            fn items(cart: &NonEmpty<LineItem>) -> &[LineItem] {
+                cart
+            }
+        }
+
+        impl<S: CheckoutStep> Sealed for super::Checkout<S> {
+            type Cart = NonEmpty<LineItem>;
+            type Form = S::Form;
+            const AT_CHECKOUT: bool = true;
+
+            fn items(cart: &NonEmpty<LineItem>) -> &[LineItem] {
+                cart
+            }
+        }
+
+        // The address isn't part of the form, as it's kept in the customer's
+        // `region` (which outlives checking out).
+        pub trait Step {
+            type Form;
+        }
+
+        impl Step for super::NeedsAddress {
+            type Form = ();
+        }
+
+        impl Step for super::NeedsShipping {
+            type Form = ();
+        }
+
+        impl Step for super::NeedsPayment {
+            type Form = ShippingOption;
+        }
+
+        impl Step for super::ReadyToPay {
+            type Form = (ShippingOption, PaymentMethod);
+        }
+    }
+
+    // An outgoing transition of a state: the method that performs it, and the
+    // name of the state it leads to. The flow is exited via "Left" and "Paid",
+    // which aren't customer states ("Paid" is where an `Order` starts instead).
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Transition {
+        pub method: &'static str,
+        pub to: &'static str,
+    }
+
+    // Behaviour shared by every state marker, which allows writing code that is
+    // generic over "any customer state" (logging, persistence etc.) without
+    // giving up the guarantees of the concrete `impl Customer<...>` blocks.
+    pub trait CustomerState: sealed::Sealed {
+        const NAME: &'static str;
+        // None of the modelled states are terminal, as the flow ends by
+        // consuming the customer rather than by entering an end state.
+        const IS_TERMINAL: bool;
+        const TRANSITIONS: &'static [Transition];
+    }
+
+    impl CustomerState for Browsing {
+        const NAME: &'static str = "Browsing";
+        const IS_TERMINAL: bool = false;
+        const TRANSITIONS: &'static [Transition] = &[
+            Transition {
+                method: "leave",
+                to: "Left",
+            },
+            Transition {
+                method: "add_item",
+                to: Shopping::NAME,
+            },
+        ];
+    }
+
+    impl CustomerState for Shopping {
+        const NAME: &'static str = "Shopping";
+        const IS_TERMINAL: bool = false;
+        const TRANSITIONS: &'static [Transition] = &[
+            Transition {
+                method: "add_item",
+                to: Shopping::NAME,
+            },
+            Transition {
+                method: "pop_item",
+                to: Shopping::NAME,
+            },
+            Transition {
+                method: "pop_item",
+                to: Browsing::NAME,
+            },
+            Transition {
+                method: "remove_item",
+                to: Shopping::NAME,
+            },
+            Transition {
+                method: "remove_item",
+                to: Browsing::NAME,
+            },
+            Transition {
+                method: "set_quantity",
+                to: Shopping::NAME,
+            },
+            Transition {
+                method: "set_quantity",
+                to: Browsing::NAME,
+            },
+            Transition {
+                method: "clear_cart",
+                to: Browsing::NAME,
+            },
+            Transition {
+                method: "proceed_to_checkout",
+                to: NeedsAddress::NAME,
+            },
+        ];
+    }
+
+    // Behaviour shared by the steps of checking out, which makes a customer
+    // checking out a `CustomerState` whichever step they're at. Like
+    // `CustomerState`, it can't be implemented outside this crate. Coupons can
+    // be applied and checking out cancelled at every step.
+    pub trait CheckoutStep: sealed::Step {
+        const NAME: &'static str;
+        const TRANSITIONS: &'static [Transition];
+    }
+
+    impl<S: CheckoutStep> CustomerState for Checkout<S> {
+        const NAME: &'static str = S::NAME;
+        const IS_TERMINAL: bool = false;
+        const TRANSITIONS: &'static [Transition] = S::TRANSITIONS;
+    }
+
+    impl CheckoutStep for NeedsAddress {
+        const NAME: &'static str = "NeedsAddress";
+        const TRANSITIONS: &'static [Transition] = &[
+            Transition {
+                method: "apply_coupon",
+                to: Self::NAME,
+            },
+            Transition {
+                method: "remove_coupon",
+                to: Self::NAME,
+            },
+            Transition {
+                method: "ship_to",
+                to: NeedsShipping::NAME,
+            },
+            Transition {
+                method: "cancel_checkout",
+                to: Shopping::NAME,
+            },
+        ];
+    }
+
+    impl CheckoutStep for NeedsShipping {
+        const NAME: &'static str = "NeedsShipping";
+        const TRANSITIONS: &'static [Transition] = &[
+            Transition {
+                method: "apply_coupon",
+                to: Self::NAME,
+            },
+            Transition {
+                method: "remove_coupon",
+                to: Self::NAME,
+            },
+            Transition {
+                method: "choose_shipping",
+                to: NeedsPayment::NAME,
+            },
+            Transition {
+                method: "cancel_checkout",
+                to: Shopping::NAME,
+            },
+        ];
+    }
+
+    impl CheckoutStep for NeedsPayment {
+        const NAME: &'static str = "NeedsPayment";
+        const TRANSITIONS: &'static [Transition] = &[
+            Transition {
+                method: "apply_coupon",
+                to: Self::NAME,
+            },
+            Transition {
+                method: "remove_coupon",
+                to: Self::NAME,
+            },
+            Transition {
+                method: "choose_payment",
+                to: ReadyToPay::NAME,
+            },
+            Transition {
+                method: "cancel_checkout",
+                to: Shopping::NAME,
+            },
+        ];
+    }
+
+    impl CheckoutStep for ReadyToPay {
+        const NAME: &'static str = "ReadyToPay";
+        const TRANSITIONS: &'static [Transition] = &[
+            Transition {
+                method: "apply_coupon",
+                to: Self::NAME,
+            },
+            Transition {
+                method: "remove_coupon",
+                to: Self::NAME,
+            },
+            Transition {
+                method: "choose_payment",
+                to: Self::NAME,
+            },
+            Transition {
+                method: "cancel_checkout",
+                to: Shopping::NAME,
+            },
+            Transition {
+                method: "finalise_payment",
+                to: "Paid",
+            },
+        ];
+    }
 
     // Representation of the online shop customer (the domain entity).
     // The fields are private so we can't instantiate it directly and would have
     // to use the exposed `visit_site()` func as the entry point.
-    pub struct Customer<S> {
-        shopping_cart: Vec<u8>,
+    pub struct Customer<S: CustomerState> {
+        id: CustomerId,
+        shop: Shop,
+        shopping_cart: S::Cart,
+        // The code of the coupon applied at checkout, which sticks around when
+        // going back to shopping.
+        coupon: Option<String>,
+        // Where the order goes, which is needed to work out the tax. Like the
+        // coupon, it sticks around when going back to shopping.
+        region: Option<Region>,
+        // The rest of the checkout form, as far as it's been filled in.
+        form: S::Form,
+        session: Session,
+        sink: Box<dyn EventSink>,
         _inner: PhantomData<S>,
     }
 
+    // Read-only accessors available in every state. None of these consume
+    // `self`, so they can't be used to sneak in a transition.
+    impl<S: CustomerState> Customer<S> {
+        pub fn state_name(&self) -> &'static str {
+            S::NAME
+        }
+
+        pub fn id(&self) -> &CustomerId {
+            &self.id
+        }
+
+        pub fn shopping_cart(&self) -> &[LineItem] {
+            S::items(&self.shopping_cart)
+        }
+
+        pub fn coupon(&self) -> Option<&str> {
+            self.coupon.as_deref()
+        }
+
+        pub fn region(&self) -> Option<&Region> {
+            self.region.as_ref()
+        }
+
+        // The coupon's discount on the cart as it is now. A coupon that doesn't
+        // apply anymore (e.g. the cart has dropped below its minimum spend)
+        // takes nothing off, but stays applied in case it applies again. It
+        // can't be paid with until it does, see `finalise_payment()`.
+        fn current_discount(&self) -> Result<Money<StoreCurrency>, MoneyError> {
+            let Some(code) = &self.coupon else {
+                return Ok(Money::zero());
+            };
+            match self
+                .shop
+                .promotions
+                .discount(&self.id, code, self.shopping_cart())
+            {
+                Ok(discount) => Ok(discount),
+                Err(CouponError::Money(err)) => Err(err),
+                Err(_) => Ok(Money::zero()),
+            }
+        }
+
+        fn current_total(&self) -> Result<Money<StoreCurrency>, MoneyError> {
+            cart::subtotal(self.shopping_cart())?.checked_sub(self.current_discount()?)
+        }
+
+        // Moves every field over into a customer of the next state, converting
+        // the cart into the one of that state along the way and filling in the
+        // checkout form as far as that state has it. This is private, so the
+        // only way to change state from the outside is still through the
+        // transitions below.
+        fn transition<T: CustomerState>(
+            self,
+            convert_cart: impl FnOnce(S::Cart) -> T::Cart,
+            form: T::Form,
+        ) -> Customer<T> {
+            Customer {
+                id: self.id,
+                shop: self.shop,
+                shopping_cart: convert_cart(self.shopping_cart),
+                coupon: self.coupon,
+                region: self.region,
+                form,
+                session: self.session,
+                sink: self.sink,
+                _inner: PhantomData,
+            }
+        }
+
+        // Tells the sink how the customer got into the current state, and
+        // keeps the session up to date with it. This is called once the cart
+        // has been updated.
+        fn record(
+            &mut self,
+            from: Option<&'static str>,
+            action: Action,
+            cart_before: Vec<LineItem>,
+        ) {
+            let cart_after = self.shopping_cart().to_vec();
+            self.session.update(&cart_after, S::AT_CHECKOUT);
+            self.sink.record(&TransitionEvent {
+                from,
+                to: S::NAME,
+                action,
+                cart_before,
+                cart_after,
+            });
+        }
+
+        // Tells the sink the customer is leaving the flow via `to`, as there's
+        // no state to record the transition into.
+        fn record_exit(&mut self, action: Action, to: &'static str) {
+            let cart = self.shopping_cart().to_vec();
+            self.sink.record(&TransitionEvent {
+                from: Some(S::NAME),
+                to,
+                action,
+                cart_before: cart.clone(),
+                cart_after: cart,
+            });
+        }
+    }
+
+    // Totals only make sense for the states with something in the cart. They're
+    // computed from the cart as it is now, so they're always up to date with
+    // whatever has been added or removed since the coupon was applied.
+    impl Customer<Shopping> {
+        pub fn subtotal(&self) -> Result<Money<StoreCurrency>, MoneyError> {
+            cart::subtotal(&self.shopping_cart)
+        }
+
+        pub fn discount(&self) -> Result<Money<StoreCurrency>, MoneyError> {
+            self.current_discount()
+        }
+
+        // What would be paid for the cart: the subtotal minus the discount.
+        pub fn total(&self) -> Result<Money<StoreCurrency>, MoneyError> {
+            self.current_total()
+        }
+    }
+
+    impl<S: CheckoutStep> Customer<Checkout<S>> {
+        pub fn subtotal(&self) -> Result<Money<StoreCurrency>, MoneyError> {
+            cart::subtotal(&self.shopping_cart)
+        }
+
+        pub fn discount(&self) -> Result<Money<StoreCurrency>, MoneyError> {
+            self.current_discount()
+        }
+
+        pub fn total(&self) -> Result<Money<StoreCurrency>, MoneyError> {
+            self.current_total()
+        }
+
+        // The tax on each line of the cart with the discount taken off, which
+        // is only known once the customer has said where to ship to with
+        // `ship_to()` (this time, or the last time they checked out).
+        pub fn tax_breakdown(&self) -> Result<TaxBreakdown, TaxError> {
+            let region = self.region.as_ref().ok_or(TaxError::NoRegion)?;
+            let discount = self.current_discount()?;
+            self.shop
+                .taxes
+                .breakdown(region, self.shopping_cart(), discount)
+        }
+
+        // What paying for the cart costs: the total with any tax that isn't
+        // included in the prices on top. Coupons come off the prices before
+        // tax, so only what's actually paid is taxed.
+        pub fn amount_due(&self) -> Result<Money<StoreCurrency>, TaxError> {
+            Ok(self.tax_breakdown()?.gross)
+        }
+    }
+
+    // The rest of the checkout form can be read back once it's filled in.
+    impl Customer<Checkout<NeedsPayment>> {
+        pub fn shipping(&self) -> ShippingOption {
+            self.form
+        }
+    }
+
+    impl Customer<Checkout<ReadyToPay>> {
+        pub fn shipping(&self) -> ShippingOption {
+            self.form.0
+        }
+
+        pub fn payment_method(&self) -> PaymentMethod {
+            self.form.1
+        }
+    }
+
     // This contains the only transitions allowed from the "Browsing" state.
     // The methods take `self` and not `&self` to disable reusing of the value
     // after the method call. If the value is meant to be reused, the methods can
     // return an instance of `Self`.
     impl Customer<Browsing> {
         // This is the only entry point to the flow, starting with "Browsing".
+        // The transitions aren't reported anywhere, see `visit_site_with()`.
         pub fn visit_site() -> Self {
-            println!("Hi site!");
-            Customer {
-                shopping_cart: vec![],
+            Self::visit_site_with(NoopSink)
+        }
+
+        // Same as `visit_site()`, but every transition from here on is
+        // reported to the given sink.
+        pub fn visit_site_with(sink: impl EventSink + 'static) -> Self {
+            Self::visit_shop(&Shop::default(), CustomerId::guest(), sink)
+        }
+
+        // Same as `visit_site_with()`, but for a known customer of a shop with
+        // its own promotions.
+        pub fn visit_shop(shop: &Shop, id: CustomerId, sink: impl EventSink + 'static) -> Self {
+            let mut customer = Customer {
+                id,
+                shop: shop.clone(),
+                shopping_cart: (),
+                coupon: None,
+                region: None,
+                form: (),
+                session: Session::new(shop.clock.now()),
+                sink: Box::new(sink),
                 _inner: PhantomData,
-            }
+            };
+            customer.record(None, Action::VisitSite, vec![]);
+            customer
         }
 
         // This consumes `self`, so after calling this func we shouldn't be able
         // to use the `Customer` value anymore, which is why we don't need to
-        // model the "Left" (end) state explicitly.
-        pub fn leave(self) {
-            println!("Not buying anything, bye site!");
+        // model the "Left" (end) state explicitly. If anything was put in the
+        // cart along the way, what's left behind is a summary of the visit.
+        // Nothing is reserved while browsing, so there's no stock to give back.
+        pub fn leave(mut self) -> Option<AbandonedSession> {
+            self.record_exit(Action::Leave, "Left");
+            if self.session.last_cart.is_empty() {
+                return None;
+            }
+            Some(AbandonedSession {
+                customer: self.id,
+                started_at: self.session.started_at,
+                left_at: self.shop.clock.now(),
+                last_cart: self.session.last_cart,
+                reached_checkout: self.session.reached_checkout,
+                coupon: self.coupon,
+                region: self.region,
+            })
         }
 
         // "Browsing" -> "Shopping"
-        pub fn add_item(mut self, item: u8) -> Customer<Shopping> {
-            self.shopping_cart.push(item);
-            println!("Added {} to cart ({:?})", item, self.shopping_cart);
-            Customer {
-                shopping_cart: self.shopping_cart,
-                _inner: PhantomData,
-            }
+        pub fn add_item(self, product: Product) -> Customer<Shopping> {
+            let line = LineItem::new(product.clone());
+            let mut shopping = self.transition(|()| NonEmpty::new(line), ());
+            shopping.record(Some(Browsing::NAME), Action::AddItem(product), vec![]);
+            shopping
         }
     }
 
+    // What's left after taking something out of the cart. Removing the last
+    // item takes the customer back to "Browsing", as there's nothing to shop
+    // for or check out anymore.
+    pub enum CartOutcome {
+        StillShopping(Customer<Shopping>),
+        Emptied(Customer<Browsing>),
+    }
+
     // This contains the only transitions allowed from the "Shopping" state.
     // The methods take `self` and not `&self` to disable reusing of the value
     // after the method call. If the value is meant to be reused, the methods can
     // return an instance of `Self`.
     impl Customer<Shopping> {
         // "Shopping" -> "Shopping"
-        pub fn add_item(mut self, item: u8) -> Self {
-            self.shopping_cart.push(item);
-            println!("Added {} to cart ({:?})", item, self.shopping_cart);
+        pub fn add_item(mut self, product: Product) -> Self {
+            let cart_before = self.shopping_cart().to_vec();
+            cart::add(&mut self.shopping_cart, product.clone());
+            self.record(Some(Shopping::NAME), Action::AddItem(product), cart_before);
             self
         }
 
-        // "Shopping" -> "Shopping"
-        pub fn pop_item(mut self) -> Self {
-            if let Some(popped) = self.shopping_cart.pop() {
-                println!("Removed {} from cart ({:?})", popped, self.shopping_cart);
-            }
-            self
+        // "Shopping" -> "Shopping", or "Shopping" -> "Browsing" when popping
+        // the last item
+        pub fn pop_item(self) -> CartOutcome {
+            self.change_cart(Action::PopItem, cart::pop)
+        }
+
+        // "Shopping" -> "Shopping", or "Shopping" -> "Browsing" when removing
+        // the last product
+        pub fn remove_item(self, sku: &Sku) -> CartOutcome {
+            self.change_cart(Action::RemoveItem(sku.clone()), |lines| {
+                cart::remove(lines, sku)
+            })
+        }
+
+        // "Shopping" -> "Shopping", or "Shopping" -> "Browsing" when setting
+        // the quantity of the last product to 0
+        pub fn set_quantity(self, sku: &Sku, quantity: u32) -> CartOutcome {
+            self.change_cart(Action::SetQuantity(sku.clone(), quantity), |lines| {
+                cart::set_quantity(lines, sku, quantity)
+            })
         }
 
         // "Shopping" -> "Browsing"
-        pub fn clear_cart(mut self) -> Customer<Browsing> {
-            self.shopping_cart.clear();
-            println!("Cart has been cleared.");
-            Customer {
-                shopping_cart: self.shopping_cart,
-                _inner: PhantomData,
+        pub fn clear_cart(self) -> Customer<Browsing> {
+            let cart_before = self.shopping_cart().to_vec();
+            let mut browsing = self.transition(|_| (), ());
+            browsing.record(Some(Shopping::NAME), Action::ClearCart, cart_before);
+            browsing
+        }
+
+        // "Shopping" -> "NeedsAddress", reserving everything in the cart until
+        // the customer pays, cancels or the reservation expires. If some of it
+        // is out of stock, nothing is reserved and the customer is handed back
+        // along with what's short.
+        pub fn proceed_to_checkout(
+            self,
+        ) -> Result<Customer<Checkout<NeedsAddress>>, (Self, OutOfStock)> {
+            if let Err(err) = self
+                .shop
+                .inventory
+                .reserve(&self.session.reservation, self.shopping_cart())
+            {
+                return Err((self, err));
             }
+            let cart_before = self.shopping_cart().to_vec();
+            let mut checkout = self.transition(|cart| cart, ());
+            checkout.record(Some(Shopping::NAME), Action::ProceedToCheckout, cart_before);
+            Ok(checkout)
         }
 
-        // "Shopping" -> "Checkout"
-        pub fn proceed_to_checkout(self) -> Customer<Checkout> {
-            println!("Proceeding to checkout.");
-            Customer {
-                shopping_cart: self.shopping_cart,
-                _inner: PhantomData,
+        // Applies a change that might take everything out of the cart, in which
+        // case the customer goes back to "Browsing".
+        fn change_cart(
+            self,
+            action: Action,
+            change: impl FnOnce(&mut Vec<LineItem>),
+        ) -> CartOutcome {
+            let cart_before = self.shopping_cart().to_vec();
+            let mut lines = cart_before.clone();
+            change(&mut lines);
+            match NonEmpty::from_vec(lines) {
+                Some(cart) => {
+                    let mut shopping = self.transition(|_| cart, ());
+                    shopping.record(Some(Shopping::NAME), action, cart_before);
+                    CartOutcome::StillShopping(shopping)
+                }
+                None => {
+                    let mut browsing = self.transition(|_| (), ());
+                    browsing.record(Some(Shopping::NAME), action, cart_before);
+                    CartOutcome::Emptied(browsing)
+                }
             }
         }
     }
 
-    // This contains the only transitions allowed from the "Checkout" state.
+    // This contains the transitions allowed at every step of checking out.
     // The methods take `self` and not `&self` to disable reusing of the value
     // after the method call. If the value is meant to be reused, the methods can
     // return an instance of `Self`.
-    impl Customer<Checkout> {
-        // "Checkout" -> "Shopping"
+    impl<S: CheckoutStep> Customer<Checkout<S>> {
+        // "NeedsAddress" | "NeedsShipping" | "NeedsPayment" | "ReadyToPay" ->
+        // "Shopping", releasing the stock reserved for the cart. The region
+        // sticks around, but the rest of the checkout form is gone.
         pub fn cancel_checkout(self) -> Customer<Shopping> {
-            println!("Cancelling checkout, continue shopping.");
-            Customer {
-                shopping_cart: self.shopping_cart,
-                _inner: PhantomData,
+            self.shop.inventory.release(&self.session.reservation);
+            let cart_before = self.shopping_cart().to_vec();
+            let mut shopping = self.transition(|cart| cart, ());
+            shopping.record(Some(S::NAME), Action::CancelCheckout, cart_before);
+            shopping
+        }
+
+        // Stays at the same step, handing the customer back along with the
+        // reason if the coupon can't be used on this cart. A coupon replaces
+        // any that was applied before.
+        pub fn apply_coupon(mut self, code: &str) -> Result<Self, (Self, CouponError)> {
+            let cart_before = self.shopping_cart().to_vec();
+            let checked = self
+                .shop
+                .promotions
+                .discount(&self.id, code, self.shopping_cart());
+            if let Err(err) = checked {
+                return Err((self, err));
             }
+            self.coupon = Some(code.to_string());
+            self.record(
+                Some(S::NAME),
+                Action::ApplyCoupon(code.to_string()),
+                cart_before,
+            );
+            Ok(self)
         }
 
-        // This, like `leave()`, also consumes `self` and returns nothing, so
-        // this transition leads to the end of the flow.
-        pub fn finalise_payment(self) {
-            println!("Done paying for the items, bye site!");
+        // Stays at the same step.
+        pub fn remove_coupon(mut self) -> Self {
+            let cart_before = self.shopping_cart().to_vec();
+            self.coupon = None;
+            self.record(Some(S::NAME), Action::RemoveCoupon, cart_before);
+            self
+        }
+    }
+
+    // The steps of checking out each fill in one part of the checkout form,
+    // which can only be done in this order.
+    impl Customer<Checkout<NeedsAddress>> {
+        // "NeedsAddress" -> "NeedsShipping", handing the customer back along
+        // with the reason if the shop can't tax the cart there (e.g. it doesn't
+        // ship to the region at all).
+        pub fn ship_to(
+            mut self,
+            region: Region,
+        ) -> Result<Customer<Checkout<NeedsShipping>>, (Self, TaxError)> {
+            let cart_before = self.shopping_cart().to_vec();
+            let checked = self
+                .shop
+                .taxes
+                .breakdown(&region, self.shopping_cart(), Money::zero());
+            if let Err(err) = checked {
+                return Err((self, err));
+            }
+            self.region = Some(region.clone());
+            let mut needs_shipping = self.transition(|cart| cart, ());
+            needs_shipping.record(
+                Some(NeedsAddress::NAME),
+                Action::ShipTo(region),
+                cart_before,
+            );
+            Ok(needs_shipping)
+        }
+    }
+
+    impl Customer<Checkout<NeedsShipping>> {
+        // "NeedsShipping" -> "NeedsPayment"
+        pub fn choose_shipping(self, option: ShippingOption) -> Customer<Checkout<NeedsPayment>> {
+            let cart_before = self.shopping_cart().to_vec();
+            let mut needs_payment = self.transition(|cart| cart, option);
+            needs_payment.record(
+                Some(NeedsShipping::NAME),
+                Action::ChooseShipping(option),
+                cart_before,
+            );
+            needs_payment
+        }
+    }
+
+    impl Customer<Checkout<NeedsPayment>> {
+        // "NeedsPayment" -> "ReadyToPay". Nothing is charged until
+        // `finalise_payment()`.
+        pub fn choose_payment(self, method: PaymentMethod) -> Customer<Checkout<ReadyToPay>> {
+            let cart_before = self.shopping_cart().to_vec();
+            let shipping = self.form;
+            let mut ready = self.transition(|cart| cart, (shipping, method));
+            ready.record(
+                Some(NeedsPayment::NAME),
+                Action::ChoosePayment(method),
+                cart_before,
+            );
+            ready
+        }
+    }
+
+    impl Customer<Checkout<ReadyToPay>> {
+        // "ReadyToPay" -> "ReadyToPay", e.g. to pay another way after the
+        // payment was declined.
+        pub fn choose_payment(mut self, method: PaymentMethod) -> Self {
+            let cart_before = self.shopping_cart().to_vec();
+            self.form.1 = method;
+            self.record(
+                Some(ReadyToPay::NAME),
+                Action::ChoosePayment(method),
+                cart_before,
+            );
+            self
+        }
+
+        // This, like `leave()`, also consumes `self`, so this transition leads
+        // to the end of the flow, leaving behind the order that was paid for.
+        // If the payment doesn't go through (or the stock has run out since
+        // the reservation expired), the customer is handed back still ready to
+        // pay, free to try again or cancel. The same goes for a coupon that
+        // doesn't apply anymore (e.g. it's expired since it was applied), as
+        // the customer would otherwise be charged more than they were shown.
+        // Otherwise the coupon counts as used by the customer.
+        pub fn finalise_payment(
+            mut self,
+            gateway: &dyn PaymentGateway,
+        ) -> Result<Order<Paid>, (Self, PaymentError)> {
+            let order = match self.charge(gateway) {
+                Ok(order) => order,
+                Err(err) => return Err((self, err)),
+            };
+            if let Some(code) = &self.coupon {
+                self.shop.promotions.redeem(&self.id, code);
+            }
+            self.record_exit(Action::FinalisePayment, "Paid");
+            Ok(order)
+        }
+
+        // Takes the amount due and the stock for good, and writes up the order
+        // for it. The stock is reserved again first, in case the reservation
+        // has expired, so that taking it shouldn't fail once the money's been
+        // taken. If it does anyway, the money is given back (if there was any
+        // to begin with, e.g. not with a coupon taking everything off).
+        fn charge(&self, gateway: &dyn PaymentGateway) -> Result<Order<Paid>, PaymentError> {
+            let (shipping, method) = self.form;
+            let subtotal = self.subtotal()?;
+            let discount = match &self.coupon {
+                Some(code) => {
+                    self.shop
+                        .promotions
+                        .discount(&self.id, code, self.shopping_cart())?
+                }
+                None => Money::zero(),
+            };
+            let tax = self.tax_breakdown()?;
+            let total = self.amount_due()?;
+            let inventory = &self.shop.inventory;
+            let reservation = &self.session.reservation;
+            inventory.reserve(reservation, self.shopping_cart())?;
+
+            let authorization = gateway.authorize(total, method)?;
+            let payment = match gateway.capture(&authorization) {
+                Ok(payment) => payment,
+                Err(err) => {
+                    // Nothing has been taken, and there's nothing more to do
+                    // if voiding fails too, as the authorization will lapse.
+                    let _ = gateway.void(&authorization);
+                    return Err(err);
+                }
+            };
+            if let Err(out_of_stock) = inventory.commit(reservation, self.shopping_cart()) {
+                if payment.amount == Money::zero() {
+                    return Err(out_of_stock.into());
+                }
+                return Err(match gateway.refund(&payment, payment.amount) {
+                    Ok(_) => out_of_stock.into(),
+                    Err(refund) => PaymentError::NotRefunded {
+                        payment,
+                        out_of_stock,
+                        refund: Box::new(refund),
+                    },
+                });
+            }
+            Ok(Order {
+                id: self.shop.next_order_id(),
+                customer: self.id.clone(),
+                placed_at: self.shop.clock.now(),
+                lines: self.shopping_cart().to_vec(),
+                coupon: self.coupon.clone(),
+                subtotal,
+                discount,
+                tax,
+                total,
+                shipping,
+                payment,
+                state: Paid,
+            })
         }
     }
 }
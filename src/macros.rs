@@ -0,0 +1,147 @@
+/// Declares a typestate machine without the hand-written boilerplate.
+///
+/// Given the entity's fields, its states and the `From -> To` edges between
+/// them, this generates:
+///
+/// - a unit struct per state, and a sealed trait (with a `NAME` const) that
+///   only those states implement,
+/// - the entity struct, generic over its state, with private fields,
+/// - a private `from_parts()` constructor and a private `transition()` helper
+///   that moves every field over into the next state, and
+/// - one `impl Entity<From>` block per edge, whose method consumes `self` and
+///   returns `Entity<To>` once its body has run.
+///
+/// The generated items include a private `sealed` module, so only one machine
+/// can be declared per module.
+///
+/// ```
+/// mod door {
+///     stated::state_machine! {
+///         pub struct Door {
+///             times_opened: u32,
+///         }
+///
+///         pub trait DoorState { Closed, Open, Locked }
+///
+///         Closed -> Open: pub fn open(self) {
+///             self.times_opened += 1;
+///         }
+///         Open -> Closed: pub fn close(self) {}
+///         Closed -> Locked: pub fn lock(self, key: u32) {
+///             println!("Locked with key {}", key);
+///         }
+///     }
+///
+///     // Entry points are written by hand, using the generated constructor.
+///     impl Door<Closed> {
+///         pub fn install() -> Self {
+///             Self::from_parts(0)
+///         }
+///     }
+///
+///     impl<S: DoorState> Door<S> {
+///         pub fn times_opened(&self) -> u32 {
+///             self.times_opened
+///         }
+///     }
+/// }
+///
+/// use door::{Door, DoorState, Open};
+///
+/// let door = Door::install().open().close().open();
+/// assert_eq!(door.times_opened(), 2);
+/// assert_eq!(Open::NAME, "Open");
+/// ```
+///
+/// Invalid transitions simply don't exist as methods:
+///
+/// ```compile_fail
+/// mod door {
+///     stated::state_machine! {
+///         pub struct Door {}
+///         pub trait DoorState { Closed, Open }
+///         Closed -> Open: pub fn open(self) {}
+///     }
+///
+///     impl Door<Closed> {
+///         pub fn install() -> Self {
+///             Self::from_parts()
+///         }
+///     }
+/// }
+///
+/// door::Door::install().open().open();
+/// ```
+#[macro_export]
+macro_rules! state_machine {
+    (
+        $(#[$entity_meta:meta])*
+        $entity_vis:vis struct $entity:ident {
+            $($field:ident: $field_ty:ty),* $(,)?
+        }
+
+        $state_vis:vis trait $state_trait:ident { $($state:ident),+ $(,)? }
+
+        $(
+            $from:ident -> $to:ident:
+                $(#[$method_meta:meta])*
+                $method_vis:vis fn $method:ident(
+                    $this:tt $(, $arg:ident: $arg_ty:ty)* $(,)?
+                ) $body:block
+        )*
+    ) => {
+        $(
+            $state_vis struct $state;
+        )+
+
+        mod sealed {
+            pub trait Sealed {}
+        }
+
+        $state_vis trait $state_trait: sealed::Sealed {
+            const NAME: &'static str;
+        }
+
+        $(
+            impl sealed::Sealed for $state {}
+
+            impl $state_trait for $state {
+                const NAME: &'static str = stringify!($state);
+            }
+        )+
+
+        $(#[$entity_meta])*
+        $entity_vis struct $entity<S: $state_trait> {
+            $($field: $field_ty,)*
+            _inner: ::core::marker::PhantomData<S>,
+        }
+
+        #[allow(dead_code)]
+        impl<S: $state_trait> $entity<S> {
+            fn from_parts($($field: $field_ty),*) -> Self {
+                $entity {
+                    $($field,)*
+                    _inner: ::core::marker::PhantomData,
+                }
+            }
+
+            fn transition<T: $state_trait>(self) -> $entity<T> {
+                $entity {
+                    $($field: self.$field,)*
+                    _inner: ::core::marker::PhantomData,
+                }
+            }
+        }
+
+        $(
+            impl $entity<$from> {
+                $(#[$method_meta])*
+                #[allow(unused_mut)]
+                $method_vis fn $method(mut $this $(, $arg: $arg_ty)*) -> $entity<$to> {
+                    $body
+                    $this.transition()
+                }
+            }
+        )*
+    };
+}
@@ -1,70 +1,277 @@
-use stated::online_shop::Customer;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::process::ExitCode;
 
-fn main() {
-    // This enables the transition `Browsing` -> `Left` via `leave()`
-    let has_sudden_change_of_plan = false;
+use stated::model::{Checker, Property};
+use stated::online_shop::{
+    self, display_cart, Action, AnyCustomer, ApplyError, Catalogue, Customer, CustomerId, Shop,
+    StdoutSink,
+};
+use stated::scenario::Scenario;
 
-    // This enables the transition `Shopping` -> `Browsing` via `clear_cart()`
-    let is_using_mums_credit_card = false;
+const USAGE: &str = "\
+Usage:
+    stated                       Go shopping interactively, one command at a time,
+                                 with the products, coupons and tax rules of the
+                                 demo shop
+    stated graph [--order] [--format <format>]
+                                 Print the state machine of the shopping flow
+                                 (or of an order once it's paid for), as dot
+                                 (default), mermaid or plantuml
+    stated run <scenario> [--json]
+                                 Run a scenario file, exiting with 1 if it fails
+                                 (or 2 if it can't be read), optionally printing
+                                 a JSON report
+    stated check [--depth <n>] [--items <n>]
+                                 Explore every path through the flow of up to n
+                                 actions (default 6), with the first n products
+                                 of the demo catalogue (default 2), printing
+                                 counterexamples to its properties";
 
-    // This enables the transition `Checkout` -> `Shopping` via `cancel_checkout()`
-    let forgot_my_wallet = false;
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
 
-    let catalogue: Vec<u8> = vec![20, 42, 36, 13, 71, 100];
-    let (first, rest_of_items) = catalogue.split_first().unwrap();
+    match args.as_slice() {
+        [] => repl(),
+        ["graph", options @ ..] => graph(options),
+        ["run", path] => run(path, false),
+        ["run", path, "--json"] | ["run", "--json", path] => run(path, true),
+        ["check", options @ ..] => check(options),
+        _ => usage_error(),
+    }
+}
 
-    // Entry point of the flow: start with "Browsing".
-    let mut browsing = Customer::visit_site();
+fn usage_error() -> ExitCode {
+    eprintln!("{}", USAGE);
+    ExitCode::from(2)
+}
 
-    if has_sudden_change_of_plan {
-        // One possible transition from "Browsing".
-        browsing.leave();
-        return;
+fn graph(options: &[&str]) -> ExitCode {
+    let (graph, options) = match options {
+        ["--order", rest @ ..] => (online_shop::order_graph(), rest),
+        _ => (online_shop::graph(), options),
+    };
+    match options {
+        [] | ["--format", "dot"] => print!("{}", graph.to_dot()),
+        ["--format", "mermaid"] => print!("{}", graph.to_mermaid()),
+        ["--format", "plantuml"] => print!("{}", graph.to_plantuml()),
+        _ => return usage_error(),
     }
+    ExitCode::SUCCESS
+}
 
-    // The other possible transition from "Browsing".
-    let mut shopping = browsing.add_item(*first);
+fn run(path: &str, json: bool) -> ExitCode {
+    let scenario = match fs::read_to_string(path) {
+        Ok(source) => Scenario::parse(&source, &Catalogue::demo()).map_err(|err| err.to_string()),
+        Err(err) => Err(err.to_string()),
+    };
+    let scenario = match scenario {
+        Ok(scenario) => scenario,
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            return ExitCode::from(2);
+        }
+    };
 
-    for item in rest_of_items {
-        // This is just some arbitrary logic to exhibit using both `add_item()`
-        // and `pop_item()`.
-        if item % 2 == 0 {
-            shopping = shopping.add_item(*item);
-        } else {
-            shopping = shopping.pop_item();
+    let report = scenario.run(&Shop::demo());
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    } else {
+        println!("{}: {}", path, report);
+    }
+
+    if report.passed {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn check(mut options: &[&str]) -> ExitCode {
+    let mut depth = 6;
+    let mut items = 2;
+    while let [flag, value, rest @ ..] = options {
+        let parsed = match *flag {
+            "--depth" => value.parse().map(|value| depth = value).ok(),
+            "--items" => value.parse().map(|value| items = value).ok(),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return usage_error();
         }
+        options = rest;
+    }
+    if !options.is_empty() {
+        return usage_error();
     }
 
-    if is_using_mums_credit_card {
-        // One possible "ending" to the flow, via clearing the cart and just leaving.
-        browsing = shopping.clear_cart();
-        browsing.leave();
-        return;
+    let products = Catalogue::demo().products().take(items).cloned().collect();
+    let report = Checker::new(depth, products).check(&Property::ALL);
+    print!("{}", report);
+    if report.passed() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+// The commands understood by `Action::parse()`, keyed by the `Customer` method
+// they call.
+const COMMANDS: [(&str, &str); 14] = [
+    ("add_item", "add <sku>"),
+    ("pop_item", "pop"),
+    ("remove_item", "remove <sku>"),
+    ("set_quantity", "set <sku> <quantity>"),
+    ("clear_cart", "clear"),
+    ("proceed_to_checkout", "checkout"),
+    ("apply_coupon", "coupon <code>"),
+    ("remove_coupon", "no-coupon"),
+    ("ship_to", "ship <region>"),
+    ("choose_shipping", "shipping <option>"),
+    ("choose_payment", "payment <method>"),
+    ("cancel_checkout", "cancel"),
+    ("finalise_payment", "pay"),
+    ("leave", "leave"),
+];
+
+// Drives a customer through the flow with commands read from stdin. Every
+// command goes through `AnyCustomer`, so only the transitions of the current
+// state are ever offered or accepted.
+fn repl() -> ExitCode {
+    let catalogue = Catalogue::demo();
+    let shop = Shop::demo();
+    let mut customer = visit(&shop);
+    let mut lines = io::stdin().lock().lines();
+
+    loop {
+        print!(
+            "[{}] {} > ",
+            describe(&customer),
+            commands(&customer).join(" | ")
+        );
+        // Without a newline the prompt only shows up once it's flushed, which
+        // fails if nobody's reading anymore (e.g. piped into `head`).
+        if let Err(err) = io::stdout().flush() {
+            eprintln!("{}", err);
+            return ExitCode::FAILURE;
+        }
+
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            Some(Err(err)) => {
+                eprintln!("{}", err);
+                return ExitCode::FAILURE;
+            }
+            None => {
+                println!();
+                return ExitCode::SUCCESS;
+            }
+        };
+        match line.trim() {
+            "" => continue,
+            "quit" => return ExitCode::SUCCESS,
+            "help" => {
+                println!("Commands: {}", commands(&customer).join(", "));
+                continue;
+            }
+            "tax" => {
+                match customer.tax_breakdown() {
+                    Some(Ok(breakdown)) => println!("{}", breakdown),
+                    Some(Err(err)) => println!("{}", err),
+                    None => println!("tax is only worked out at checkout"),
+                }
+                continue;
+            }
+            "products" => {
+                for product in catalogue.products() {
+                    println!(
+                        "{:<8}{:<20}{:>12}",
+                        product.sku, product.name, product.unit_price
+                    );
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        let action = match Action::parse(&line, &catalogue) {
+            Ok(action) => action,
+            Err(err) => {
+                println!("{}, try one of: {}", err, commands(&customer).join(", "));
+                continue;
+            }
+        };
+        if action == Action::VisitSite && customer.is_terminal() {
+            customer = visit(&shop);
+        } else {
+            customer = match customer.apply(action) {
+                Ok(AnyCustomer::Paid(order)) => {
+                    println!("{}", order.receipt());
+                    AnyCustomer::Paid(order)
+                }
+                Ok(next) => next,
+                Err(ApplyError::Rejected { customer, reason }) => {
+                    println!("{}", reason);
+                    customer
+                }
+                Err(ApplyError::Invalid { customer, err }) => {
+                    println!("{}, try one of: {}", err, commands(&customer).join(", "));
+                    customer
+                }
+            };
+        }
     }
+}
+
+fn visit(shop: &Shop) -> AnyCustomer {
+    Customer::visit_shop(shop, CustomerId::guest(), StdoutSink).into()
+}
 
-    // The other possible "ending" to the flow, where we actually proceed with
-    // checkout and then leave.
-    let checkout = shopping.proceed_to_checkout();
-
-    if forgot_my_wallet {
-        // This demonstrates another branch where instead of just going forwards,
-        // we backtrack.
-        shopping = checkout.cancel_checkout();
-        browsing = shopping.clear_cart();
-        browsing.leave();
-        return;
+fn describe(customer: &AnyCustomer) -> String {
+    match customer.shopping_cart() {
+        [] => customer.state_name().to_string(),
+        cart => {
+            let mut description = format!("{} {}", customer.state_name(), display_cart(cart));
+            // Once it's known where the order goes, that includes the tax.
+            let total = match customer.amount_due() {
+                Some(amount_due) if customer.region().is_some() => amount_due.ok(),
+                _ => customer.total().ok(),
+            };
+            if let Some(total) = total {
+                description += &format!(" {}", total);
+            }
+            if let Some(code) = customer.coupon() {
+                description += &format!(" with {}", code);
+            }
+            if let Some(region) = customer.region() {
+                description += &format!(" to {}", region);
+            }
+            description
+        }
     }
+}
 
-    checkout.finalise_payment();
-
-    // This "default" flow results in this output:
-    // Hi site!
-    // Added 20 to cart ([20])
-    // Added 42 to cart ([20, 42])
-    // Added 36 to cart ([20, 42, 36])
-    // Removed 36 from cart ([20, 42])
-    // Removed 42 from cart ([20])
-    // Added 100 to cart ([20, 100])
-    // Proceeding to checkout.
-    // Done paying for the items, bye site!
+// The commands that are valid right now, as shown in the prompt.
+fn commands(customer: &AnyCustomer) -> Vec<&'static str> {
+    let mut commands: Vec<&str> = COMMANDS
+        .iter()
+        .filter(|(method, _)| {
+            customer
+                .transitions()
+                .iter()
+                .any(|transition| transition.method == *method)
+        })
+        .map(|(_, command)| *command)
+        .collect();
+    if customer.is_terminal() {
+        commands.push("visit");
+    }
+    if customer.tax_breakdown().is_some() {
+        commands.push("tax");
+    }
+    commands.push("products");
+    commands.push("quit");
+    commands
 }
@@ -0,0 +1,413 @@
+// A bounded model checker for the shopping flow. The typestates prove which
+// transitions exist, this explores every sequence of them (up to a depth, and
+// with carts drawn from a small set of products) to prove things about the
+// paths.
+//
+// The explored graph is a runtime mirror of the `Customer` one, with the cart
+// contents as part of each node. It's built by running the actual transitions
+// through `AnyCustomer`, so it can't disagree with the code.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::online_shop::{
+    self, display_cart, Action, AnyCustomer, ApplyError, Customer, CustomerId, LineItem, NoopSink,
+    PaymentMethod, Product, Region, ShippingOption, Shop, TaxTable,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Property {
+    // From everywhere reachable, the customer can still leave or pay.
+    AlwaysTerminates,
+    // `finalise_payment()` can't be called with nothing in the cart.
+    NoEmptyPayment,
+    // Every state of `online_shop::graph()` is reached by some path.
+    AllStatesReachable,
+    // Every action the customer's state allows goes through, e.g. there's a
+    // tax rule for every product in the region that's shipped to.
+    NeverRefused,
+}
+
+impl Property {
+    pub const ALL: [Property; 4] = [
+        Property::AlwaysTerminates,
+        Property::NoEmptyPayment,
+        Property::AllStatesReachable,
+        Property::NeverRefused,
+    ];
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Property::AlwaysTerminates => "every path can still end by leaving or paying",
+            Property::NoEmptyPayment => "finalise_payment is never reached with an empty cart",
+            Property::AllStatesReachable => "every state is reachable",
+            Property::NeverRefused => "allowed actions are never refused",
+        }
+    }
+}
+
+// A node of the explored graph: the customer's state along with its cart.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Config {
+    pub state: &'static str,
+    pub cart: Vec<LineItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub property: Property,
+    pub message: String,
+    // The shortest sequence of actions (after visiting the site) leading to
+    // the violation, empty if there's nothing to show for it.
+    pub trace: Vec<Action>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub depth: usize,
+    pub products: Vec<Product>,
+    pub configs: usize,
+    pub paths: u128,
+    pub results: Vec<(Property, Option<Violation>)>,
+}
+
+impl Report {
+    pub fn passed(&self) -> bool {
+        self.results
+            .iter()
+            .all(|(_, violation)| violation.is_none())
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let skus: Vec<String> = self
+            .products
+            .iter()
+            .map(|product| product.sku.to_string())
+            .collect();
+        writeln!(
+            f,
+            "Explored {} configurations and {} paths up to depth {}, with products {}",
+            self.configs,
+            self.paths,
+            self.depth,
+            skus.join(", ")
+        )?;
+        for (property, violation) in &self.results {
+            match violation {
+                None => writeln!(f, "ok      {}", property.description())?,
+                Some(violation) => {
+                    writeln!(f, "FAILED  {}", property.description())?;
+                    writeln!(f, "        {}", violation.message)?;
+                    if !violation.trace.is_empty() {
+                        let steps: Vec<String> = std::iter::once(Action::VisitSite)
+                            .chain(violation.trace.iter().cloned())
+                            .map(|action| action.to_string())
+                            .collect();
+                        writeln!(f, "        counterexample: {}", steps.join(" -> "))?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct Checker {
+    depth: usize,
+    products: Vec<Product>,
+    taxes: TaxTable,
+}
+
+struct Node {
+    config: Config,
+    depth: usize,
+    // The shortest way here, thanks to exploring breadth first.
+    trace: Vec<Action>,
+    successors: Vec<(Action, usize)>,
+    // Allowed actions that refused to happen, with the reason why. They lead
+    // nowhere, as the customer is handed back as it was.
+    refused: Vec<(Action, String)>,
+}
+
+impl Checker {
+    // `depth` bounds the number of actions after visiting the site, and
+    // `products` is everything that can be added to the cart. Customers shop
+    // with the demo tax rules, so there's somewhere to ship to.
+    pub fn new(depth: usize, products: Vec<Product>) -> Self {
+        Checker {
+            depth,
+            products,
+            taxes: TaxTable::demo(),
+        }
+    }
+
+    pub fn check(&self, properties: &[Property]) -> Report {
+        // Exploring twice as deep as asked means every configuration within
+        // the bound gets `depth` more steps to show that it can terminate.
+        let nodes = self.explore(self.depth * 2);
+
+        let results = properties
+            .iter()
+            .map(|&property| {
+                let violation = match property {
+                    Property::AlwaysTerminates => self.check_terminates(&nodes),
+                    Property::NoEmptyPayment => self.check_no_empty_payment(&nodes),
+                    Property::AllStatesReachable => self.check_all_states_reachable(&nodes),
+                    Property::NeverRefused => self.check_never_refused(&nodes),
+                };
+                (property, violation)
+            })
+            .collect();
+
+        Report {
+            depth: self.depth,
+            products: self.products.clone(),
+            configs: nodes.iter().filter(|node| node.depth <= self.depth).count(),
+            paths: count_paths(&nodes, self.depth),
+            results,
+        }
+    }
+
+    fn actions(&self) -> Vec<Action> {
+        // Setting a quantity to 0 and to something other than 1 is enough to
+        // cover both ways it can go.
+        let mut actions: Vec<Action> = self
+            .products
+            .iter()
+            .flat_map(|product| {
+                [
+                    Action::AddItem(product.clone()),
+                    Action::RemoveItem(product.sku.clone()),
+                    Action::SetQuantity(product.sku.clone(), 0),
+                    Action::SetQuantity(product.sku.clone(), 2),
+                ]
+            })
+            .collect();
+        // Whether a coupon applies or a region can be shipped to depends on the
+        // promotions and tax rules of the shop, not on the flow, so there are
+        // no coupons to apply (only to remove) and only one region to ship to,
+        // which taxes the demo catalogue (products of other tax categories
+        // can't be shipped at all). The shop doesn't track any stock either and
+        // accepts every payment, so checking out and paying are never refused,
+        // whichever way it's shipped and paid.
+        actions.extend([
+            Action::PopItem,
+            Action::ClearCart,
+            Action::ProceedToCheckout,
+            Action::RemoveCoupon,
+            Action::ShipTo(Region::from("NL")),
+            Action::ChooseShipping(ShippingOption::Standard),
+            Action::ChoosePayment(PaymentMethod::Card),
+            Action::CancelCheckout,
+            Action::FinalisePayment,
+            Action::Leave,
+        ]);
+        actions
+    }
+
+    fn explore(&self, max_depth: usize) -> Vec<Node> {
+        let actions = self.actions();
+        let start = match self.run(&[]) {
+            Ok(customer) => config_of(&customer),
+            Err(_) => unreachable!("visiting the site takes no actions"),
+        };
+        let mut index = HashMap::from([(start.clone(), 0)]);
+        let mut nodes = vec![Node {
+            config: start,
+            depth: 0,
+            trace: vec![],
+            successors: vec![],
+            refused: vec![],
+        }];
+
+        // `nodes` doubles as the queue, as they're pushed in breadth-first order.
+        let mut next = 0;
+        while next < nodes.len() {
+            if nodes[next].depth == max_depth {
+                break;
+            }
+            for action in &actions {
+                let mut trace = nodes[next].trace.clone();
+                trace.push(action.clone());
+                // The way here was run before, so only the last action can
+                // fail.
+                let config = match self.run(&trace) {
+                    Ok(customer) => config_of(&customer),
+                    Err(ApplyError::Invalid { .. }) => continue,
+                    Err(ApplyError::Rejected { reason, .. }) => {
+                        nodes[next]
+                            .refused
+                            .push((action.clone(), reason.to_string()));
+                        continue;
+                    }
+                };
+
+                let successor = *index.entry(config.clone()).or_insert_with(|| {
+                    nodes.push(Node {
+                        config,
+                        depth: nodes[next].depth + 1,
+                        trace,
+                        successors: vec![],
+                        refused: vec![],
+                    });
+                    nodes.len() - 1
+                });
+                nodes[next].successors.push((action.clone(), successor));
+            }
+            next += 1;
+        }
+        nodes
+    }
+
+    // Re-runs the actions on a fresh customer of a fresh shop, stopping at the
+    // first one that isn't allowed or is refused. `AnyCustomer` can't be
+    // cloned, so this is how a configuration is revisited to try another
+    // action from it.
+    fn run(&self, trace: &[Action]) -> Result<AnyCustomer, ApplyError> {
+        let shop = Shop::new().with_taxes(self.taxes.clone());
+        let customer = Customer::visit_shop(&shop, CustomerId::guest(), NoopSink);
+        trace
+            .iter()
+            .try_fold(customer.into(), |customer: AnyCustomer, action| {
+                customer.apply(action.clone())
+            })
+    }
+
+    fn check_terminates(&self, nodes: &[Node]) -> Option<Violation> {
+        // Walk backwards from the terminal configurations to find everything
+        // that can reach one.
+        let terminals: Vec<&str> = online_shop::graph()
+            .nodes
+            .iter()
+            .filter(|node| node.is_terminal)
+            .map(|node| node.name)
+            .collect();
+        let mut can_terminate: Vec<bool> = nodes
+            .iter()
+            .map(|node| terminals.contains(&node.config.state))
+            .collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (i, node) in nodes.iter().enumerate() {
+                if !can_terminate[i] && node.successors.iter().any(|&(_, to)| can_terminate[to]) {
+                    can_terminate[i] = true;
+                    changed = true;
+                }
+            }
+        }
+
+        nodes
+            .iter()
+            .zip(can_terminate)
+            .find(|(node, can_terminate)| node.depth <= self.depth && !can_terminate)
+            .map(|(node, _)| Violation {
+                property: Property::AlwaysTerminates,
+                message: format!(
+                    "stuck in {} with cart {}, no way to leave or pay within {} steps",
+                    node.config.state,
+                    display_cart(&node.config.cart),
+                    self.depth
+                ),
+                trace: node.trace.clone(),
+            })
+    }
+
+    fn check_no_empty_payment(&self, nodes: &[Node]) -> Option<Violation> {
+        nodes
+            .iter()
+            .filter(|node| node.depth < self.depth && node.config.cart.is_empty())
+            .find_map(|node| {
+                node.successors
+                    .iter()
+                    .find(|(action, _)| *action == Action::FinalisePayment)
+                    .map(|(action, _)| {
+                        let mut trace = node.trace.clone();
+                        trace.push(action.clone());
+                        Violation {
+                            property: Property::NoEmptyPayment,
+                            message: "paid for an empty cart".to_string(),
+                            trace,
+                        }
+                    })
+            })
+    }
+
+    fn check_all_states_reachable(&self, nodes: &[Node]) -> Option<Violation> {
+        let unreachable: Vec<&str> = online_shop::graph()
+            .nodes
+            .iter()
+            .map(|node| node.name)
+            .filter(|&state| {
+                !nodes
+                    .iter()
+                    .any(|node| node.depth <= self.depth && node.config.state == state)
+            })
+            .collect();
+
+        (!unreachable.is_empty()).then(|| Violation {
+            property: Property::AllStatesReachable,
+            message: format!("never reached {}", unreachable.join(", ")),
+            trace: vec![],
+        })
+    }
+
+    fn check_never_refused(&self, nodes: &[Node]) -> Option<Violation> {
+        nodes
+            .iter()
+            .filter(|node| node.depth < self.depth)
+            .find_map(|node| {
+                let (action, reason) = node.refused.first()?;
+                let mut trace = node.trace.clone();
+                trace.push(action.clone());
+                Some(Violation {
+                    property: Property::NeverRefused,
+                    message: format!(
+                        "{} was refused in {} with cart {}: {}",
+                        action.method(),
+                        node.config.state,
+                        display_cart(&node.config.cart),
+                        reason
+                    ),
+                    trace,
+                })
+            })
+    }
+}
+
+fn config_of(customer: &AnyCustomer) -> Config {
+    Config {
+        state: customer.state_name(),
+        cart: customer.shopping_cart().to_vec(),
+    }
+}
+
+// The number of distinct action sequences of up to `depth` actions, each one
+// stopping either at a terminal configuration or at the bound.
+fn count_paths(nodes: &[Node], depth: usize) -> u128 {
+    let mut memo = HashMap::new();
+    count_paths_from(nodes, 0, depth, &mut memo)
+}
+
+fn count_paths_from(
+    nodes: &[Node],
+    node: usize,
+    remaining: usize,
+    memo: &mut HashMap<(usize, usize), u128>,
+) -> u128 {
+    if remaining == 0 || nodes[node].successors.is_empty() {
+        return 1;
+    }
+    if let Some(&count) = memo.get(&(node, remaining)) {
+        return count;
+    }
+    let count = nodes[node]
+        .successors
+        .iter()
+        .map(|&(_, to)| count_paths_from(nodes, to, remaining - 1, memo))
+        .sum();
+    memo.insert((node, remaining), count);
+    count
+}
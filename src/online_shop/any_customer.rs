@@ -0,0 +1,221 @@
+use super::{
+    AbandonedSession, Browsing, CartOutcome, Checkout, Customer, CustomerState, LineItem, Money,
+    MoneyError, NeedsAddress, NeedsPayment, NeedsShipping, Order, Paid, ReadyToPay, Region,
+    Shopping, StoreCurrency, TaxBreakdown, TaxError, Transition,
+};
+
+// A customer whose state is only known at runtime, e.g. one that's been stored
+// in a `HashMap` between requests alongside customers in other states. Going
+// back to a typed `Customer<S>` is done through the fallible `try_into_*()`
+// funcs, as only the concrete types expose the transitions.
+//
+// Unlike the typed API, a runtime value needs something to become once the
+// customer has left or paid, hence the two extra (terminal) variants. They hold
+// on to whatever the customer left behind. There's a variant per step of
+// checking out, as each of them allows different transitions.
+pub enum AnyCustomer {
+    Browsing(Customer<Browsing>),
+    Shopping(Customer<Shopping>),
+    NeedsAddress(Customer<Checkout<NeedsAddress>>),
+    NeedsShipping(Customer<Checkout<NeedsShipping>>),
+    NeedsPayment(Customer<Checkout<NeedsPayment>>),
+    ReadyToPay(Customer<Checkout<ReadyToPay>>),
+    Left(Option<AbandonedSession>),
+    Paid(Order<Paid>),
+}
+
+impl AnyCustomer {
+    pub fn state_name(&self) -> &'static str {
+        match self {
+            AnyCustomer::Browsing(customer) => customer.state_name(),
+            AnyCustomer::Shopping(customer) => customer.state_name(),
+            AnyCustomer::NeedsAddress(customer) => customer.state_name(),
+            AnyCustomer::NeedsShipping(customer) => customer.state_name(),
+            AnyCustomer::NeedsPayment(customer) => customer.state_name(),
+            AnyCustomer::ReadyToPay(customer) => customer.state_name(),
+            AnyCustomer::Left(_) => "Left",
+            AnyCustomer::Paid(_) => "Paid",
+        }
+    }
+
+    // Empty once the customer has left or paid, as the cart is gone by then.
+    pub fn shopping_cart(&self) -> &[LineItem] {
+        match self {
+            AnyCustomer::Browsing(customer) => customer.shopping_cart(),
+            AnyCustomer::Shopping(customer) => customer.shopping_cart(),
+            AnyCustomer::NeedsAddress(customer) => customer.shopping_cart(),
+            AnyCustomer::NeedsShipping(customer) => customer.shopping_cart(),
+            AnyCustomer::NeedsPayment(customer) => customer.shopping_cart(),
+            AnyCustomer::ReadyToPay(customer) => customer.shopping_cart(),
+            AnyCustomer::Left(_) | AnyCustomer::Paid(_) => &[],
+        }
+    }
+
+    // What would be paid for the cart, which is nothing once the customer has
+    // left or paid.
+    pub fn total(&self) -> Result<Money<StoreCurrency>, MoneyError> {
+        match self {
+            AnyCustomer::Browsing(customer) => customer.current_total(),
+            AnyCustomer::Shopping(customer) => customer.current_total(),
+            AnyCustomer::NeedsAddress(customer) => customer.current_total(),
+            AnyCustomer::NeedsShipping(customer) => customer.current_total(),
+            AnyCustomer::NeedsPayment(customer) => customer.current_total(),
+            AnyCustomer::ReadyToPay(customer) => customer.current_total(),
+            AnyCustomer::Left(_) | AnyCustomer::Paid(_) => Ok(Money::zero()),
+        }
+    }
+
+    pub fn coupon(&self) -> Option<&str> {
+        match self {
+            AnyCustomer::Browsing(customer) => customer.coupon(),
+            AnyCustomer::Shopping(customer) => customer.coupon(),
+            AnyCustomer::NeedsAddress(customer) => customer.coupon(),
+            AnyCustomer::NeedsShipping(customer) => customer.coupon(),
+            AnyCustomer::NeedsPayment(customer) => customer.coupon(),
+            AnyCustomer::ReadyToPay(customer) => customer.coupon(),
+            AnyCustomer::Left(_) | AnyCustomer::Paid(_) => None,
+        }
+    }
+
+    pub fn region(&self) -> Option<&Region> {
+        match self {
+            AnyCustomer::Browsing(customer) => customer.region(),
+            AnyCustomer::Shopping(customer) => customer.region(),
+            AnyCustomer::NeedsAddress(customer) => customer.region(),
+            AnyCustomer::NeedsShipping(customer) => customer.region(),
+            AnyCustomer::NeedsPayment(customer) => customer.region(),
+            AnyCustomer::ReadyToPay(customer) => customer.region(),
+            AnyCustomer::Left(_) | AnyCustomer::Paid(_) => None,
+        }
+    }
+
+    // The tax on the cart, which is only worked out at checkout (so `None`
+    // at any other point).
+    pub fn tax_breakdown(&self) -> Option<Result<TaxBreakdown, TaxError>> {
+        match self {
+            AnyCustomer::NeedsAddress(customer) => Some(customer.tax_breakdown()),
+            AnyCustomer::NeedsShipping(customer) => Some(customer.tax_breakdown()),
+            AnyCustomer::NeedsPayment(customer) => Some(customer.tax_breakdown()),
+            AnyCustomer::ReadyToPay(customer) => Some(customer.tax_breakdown()),
+            _ => None,
+        }
+    }
+
+    // What paying for the cart costs with the tax, at checkout.
+    pub fn amount_due(&self) -> Option<Result<Money<StoreCurrency>, TaxError>> {
+        match self {
+            AnyCustomer::NeedsAddress(customer) => Some(customer.amount_due()),
+            AnyCustomer::NeedsShipping(customer) => Some(customer.amount_due()),
+            AnyCustomer::NeedsPayment(customer) => Some(customer.amount_due()),
+            AnyCustomer::ReadyToPay(customer) => Some(customer.amount_due()),
+            _ => None,
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, AnyCustomer::Left(_) | AnyCustomer::Paid(_))
+    }
+
+    // The outgoing transitions of the current state, straight from the state
+    // markers. The terminal variants have none.
+    pub fn transitions(&self) -> &'static [Transition] {
+        match self {
+            AnyCustomer::Browsing(_) => Browsing::TRANSITIONS,
+            AnyCustomer::Shopping(_) => Shopping::TRANSITIONS,
+            AnyCustomer::NeedsAddress(_) => Checkout::<NeedsAddress>::TRANSITIONS,
+            AnyCustomer::NeedsShipping(_) => Checkout::<NeedsShipping>::TRANSITIONS,
+            AnyCustomer::NeedsPayment(_) => Checkout::<NeedsPayment>::TRANSITIONS,
+            AnyCustomer::ReadyToPay(_) => Checkout::<ReadyToPay>::TRANSITIONS,
+            AnyCustomer::Left(_) | AnyCustomer::Paid(_) => &[],
+        }
+    }
+
+    // These hand the value back on mismatch, so a customer in another state
+    // isn't lost just because we guessed wrong.
+    pub fn try_into_browsing(self) -> Result<Customer<Browsing>, Self> {
+        match self {
+            AnyCustomer::Browsing(customer) => Ok(customer),
+            other => Err(other),
+        }
+    }
+
+    pub fn try_into_shopping(self) -> Result<Customer<Shopping>, Self> {
+        match self {
+            AnyCustomer::Shopping(customer) => Ok(customer),
+            other => Err(other),
+        }
+    }
+
+    pub fn try_into_needs_address(self) -> Result<Customer<Checkout<NeedsAddress>>, Self> {
+        match self {
+            AnyCustomer::NeedsAddress(customer) => Ok(customer),
+            other => Err(other),
+        }
+    }
+
+    pub fn try_into_needs_shipping(self) -> Result<Customer<Checkout<NeedsShipping>>, Self> {
+        match self {
+            AnyCustomer::NeedsShipping(customer) => Ok(customer),
+            other => Err(other),
+        }
+    }
+
+    pub fn try_into_needs_payment(self) -> Result<Customer<Checkout<NeedsPayment>>, Self> {
+        match self {
+            AnyCustomer::NeedsPayment(customer) => Ok(customer),
+            other => Err(other),
+        }
+    }
+
+    pub fn try_into_ready_to_pay(self) -> Result<Customer<Checkout<ReadyToPay>>, Self> {
+        match self {
+            AnyCustomer::ReadyToPay(customer) => Ok(customer),
+            other => Err(other),
+        }
+    }
+}
+
+impl From<Customer<Browsing>> for AnyCustomer {
+    fn from(customer: Customer<Browsing>) -> Self {
+        AnyCustomer::Browsing(customer)
+    }
+}
+
+impl From<Customer<Shopping>> for AnyCustomer {
+    fn from(customer: Customer<Shopping>) -> Self {
+        AnyCustomer::Shopping(customer)
+    }
+}
+
+impl From<Customer<Checkout<NeedsAddress>>> for AnyCustomer {
+    fn from(customer: Customer<Checkout<NeedsAddress>>) -> Self {
+        AnyCustomer::NeedsAddress(customer)
+    }
+}
+
+impl From<Customer<Checkout<NeedsShipping>>> for AnyCustomer {
+    fn from(customer: Customer<Checkout<NeedsShipping>>) -> Self {
+        AnyCustomer::NeedsShipping(customer)
+    }
+}
+
+impl From<Customer<Checkout<NeedsPayment>>> for AnyCustomer {
+    fn from(customer: Customer<Checkout<NeedsPayment>>) -> Self {
+        AnyCustomer::NeedsPayment(customer)
+    }
+}
+
+impl From<Customer<Checkout<ReadyToPay>>> for AnyCustomer {
+    fn from(customer: Customer<Checkout<ReadyToPay>>) -> Self {
+        AnyCustomer::ReadyToPay(customer)
+    }
+}
+
+impl From<CartOutcome> for AnyCustomer {
+    fn from(outcome: CartOutcome) -> Self {
+        match outcome {
+            CartOutcome::StillShopping(customer) => customer.into(),
+            CartOutcome::Emptied(customer) => customer.into(),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use std::fmt;
+use std::num::NonZeroU32;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Money, MoneyError, NonEmpty, Product, Sku, StoreCurrency};
+
+// A line of the cart: a product, and how many of it. There's never a line for
+// none of a product, it's removed from the cart instead.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct LineItem {
+    pub product: Product,
+    pub quantity: NonZeroU32,
+}
+
+impl LineItem {
+    pub fn new(product: Product) -> Self {
+        LineItem {
+            product,
+            quantity: NonZeroU32::MIN,
+        }
+    }
+
+    // The unit price times the quantity.
+    pub fn total(&self) -> Result<Money<StoreCurrency>, MoneyError> {
+        self.product
+            .unit_price
+            .checked_mul(i64::from(self.quantity.get()))
+    }
+}
+
+impl fmt::Display for LineItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} x {}", self.quantity, self.product.sku)
+    }
+}
+
+// Formats the lines of a cart as e.g. "[2 x mug, 1 x tea]".
+pub fn display_cart(lines: &[LineItem]) -> impl fmt::Display + '_ {
+    struct Lines<'a>(&'a [LineItem]);
+
+    impl fmt::Display for Lines<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "[")?;
+            for (i, line) in self.0.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", line)?;
+            }
+            write!(f, "]")
+        }
+    }
+
+    Lines(lines)
+}
+
+pub(super) fn subtotal(cart: &[LineItem]) -> Result<Money<StoreCurrency>, MoneyError> {
+    let totals = cart
+        .iter()
+        .map(LineItem::total)
+        .collect::<Result<Vec<_>, _>>()?;
+    Money::sum(totals)
+}
+
+// The changes that can be made to a cart. Adding can't empty the cart, so it
+// works on a `NonEmpty` directly, while the others might and so work on a copy
+// of the lines that's checked for emptiness afterwards.
+
+// Adding a product that's already in the cart bumps the quantity of its line.
+pub(super) fn add(cart: &mut NonEmpty<LineItem>, product: Product) {
+    match cart.iter_mut().find(|line| line.product.sku == product.sku) {
+        Some(line) => line.quantity = line.quantity.saturating_add(1),
+        None => cart.push(LineItem::new(product)),
+    }
+}
+
+// Takes one off the quantity of the last line. That's the line of the product
+// most recently added for the first time, as adding more of a product that's
+// already in the cart bumps its line where it is.
+pub(super) fn pop(lines: &mut Vec<LineItem>) {
+    if let Some(line) = lines.last_mut() {
+        match NonZeroU32::new(line.quantity.get() - 1) {
+            Some(quantity) => line.quantity = quantity,
+            None => {
+                lines.pop();
+            }
+        }
+    }
+}
+
+pub(super) fn remove(lines: &mut Vec<LineItem>, sku: &Sku) {
+    lines.retain(|line| line.product.sku != *sku);
+}
+
+// Setting the quantity of a product that isn't in the cart does nothing, and
+// setting it to 0 is the same as removing it.
+pub(super) fn set_quantity(lines: &mut Vec<LineItem>, sku: &Sku, quantity: u32) {
+    match NonZeroU32::new(quantity) {
+        Some(quantity) => {
+            for line in lines.iter_mut().filter(|line| line.product.sku == *sku) {
+                line.quantity = quantity;
+            }
+        }
+        None => remove(lines, sku),
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Currency, Money, StoreCurrency, TaxCategory};
+
+// A stock keeping unit, identifying a product (including its variant, e.g. a
+// specific size or colour).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Sku(String);
+
+impl Sku {
+    pub fn new(sku: impl Into<String>) -> Self {
+        Sku(sku.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Sku {
+    fn from(sku: &str) -> Self {
+        Sku::new(sku)
+    }
+}
+
+impl fmt::Display for Sku {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&self.0)
+    }
+}
+
+// A product priced in `C`, which is the store's currency unless said otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct Product<C: Currency = StoreCurrency> {
+    pub sku: Sku,
+    pub name: String,
+    pub unit_price: Money<C>,
+    #[serde(default)]
+    pub tax_category: TaxCategory,
+    // Anything else worth knowing, e.g. "colour" or "size".
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl<C: Currency> Product<C> {
+    pub fn new(sku: impl Into<Sku>, name: impl Into<String>, unit_price: Money<C>) -> Self {
+        Product {
+            sku: sku.into(),
+            name: name.into(),
+            unit_price,
+            tax_category: TaxCategory::default(),
+            attributes: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_tax_category(mut self, category: impl Into<TaxCategory>) -> Self {
+        self.tax_category = category.into();
+        self
+    }
+
+    pub fn with_attribute(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attributes.insert(key.into(), value.into());
+        self
+    }
+}
+
+// The products on sale, looked up by SKU.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Catalogue {
+    products: BTreeMap<Sku, Product>,
+}
+
+impl Catalogue {
+    pub fn new(products: impl IntoIterator<Item = Product>) -> Self {
+        Catalogue {
+            products: products
+                .into_iter()
+                .map(|product| (product.sku.clone(), product))
+                .collect(),
+        }
+    }
+
+    // The catalogue used by the `stated` binary and the checked-in scenarios.
+    pub fn demo() -> Self {
+        let eur = Money::from_minor;
+        Catalogue::new([
+            Product::new("tea", "Loose leaf tea", eur(450)).with_tax_category("food"),
+            Product::new("mug", "Enamel mug", eur(1200)).with_attribute("colour", "blue"),
+            Product::new("socks", "Wool socks", eur(900)).with_attribute("size", "M"),
+            Product::new("book", "Field guide", eur(2450)).with_tax_category("books"),
+            Product::new("lamp", "Desk lamp", eur(3999)),
+            Product::new("plant", "Potted fern", eur(1500)),
+        ])
+    }
+
+    pub fn get(&self, sku: &Sku) -> Option<&Product> {
+        self.products.get(sku)
+    }
+
+    // In SKU order.
+    pub fn products(&self) -> impl Iterator<Item = &Product> {
+        self.products.values()
+    }
+}
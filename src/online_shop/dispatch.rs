@@ -0,0 +1,375 @@
+use std::error::Error;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    AnyCustomer, Catalogue, Checkout, CheckoutStep, CouponError, Customer, OutOfStock,
+    PaymentError, PaymentMethod, Product, Region, ShippingOption, Sku, TaxError,
+};
+
+// Every transition of `Customer<Browsing | Shopping | Checkout<..>>`, as a
+// value.
+// This is the entry point for event-driven callers (queues, HTTP handlers, UI
+// button clicks) that only find out at runtime what the customer wants to do.
+//
+// `VisitSite` only exists so that entering the flow can be described like any
+// other transition, it's never valid to `apply()` as no state lists it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+    VisitSite,
+    AddItem(Product),
+    PopItem,
+    RemoveItem(Sku),
+    SetQuantity(Sku, u32),
+    ClearCart,
+    ProceedToCheckout,
+    ApplyCoupon(String),
+    RemoveCoupon,
+    ShipTo(Region),
+    ChooseShipping(ShippingOption),
+    ChoosePayment(PaymentMethod),
+    CancelCheckout,
+    FinalisePayment,
+    Leave,
+}
+
+impl Action {
+    // The name of the `Customer` method this action calls, as listed in the
+    // states' `TRANSITIONS`.
+    pub fn method(&self) -> &'static str {
+        match self {
+            Action::VisitSite => "visit_site",
+            Action::AddItem(_) => "add_item",
+            Action::PopItem => "pop_item",
+            Action::RemoveItem(_) => "remove_item",
+            Action::SetQuantity(..) => "set_quantity",
+            Action::ClearCart => "clear_cart",
+            Action::ProceedToCheckout => "proceed_to_checkout",
+            Action::ApplyCoupon(_) => "apply_coupon",
+            Action::RemoveCoupon => "remove_coupon",
+            Action::ShipTo(_) => "ship_to",
+            Action::ChooseShipping(_) => "choose_shipping",
+            Action::ChoosePayment(_) => "choose_payment",
+            Action::CancelCheckout => "cancel_checkout",
+            Action::FinalisePayment => "finalise_payment",
+            Action::Leave => "leave",
+        }
+    }
+}
+
+// Actions as short commands, e.g. "add mug" or "checkout", which is how they're
+// typed in by users of the `stated` binary. `Action::parse()` reads them back.
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::VisitSite => write!(f, "visit"),
+            Action::AddItem(product) => write!(f, "add {}", product.sku),
+            Action::PopItem => write!(f, "pop"),
+            Action::RemoveItem(sku) => write!(f, "remove {}", sku),
+            Action::SetQuantity(sku, quantity) => write!(f, "set {} {}", sku, quantity),
+            Action::ClearCart => write!(f, "clear"),
+            Action::ProceedToCheckout => write!(f, "checkout"),
+            Action::ApplyCoupon(code) => write!(f, "coupon {}", code),
+            Action::RemoveCoupon => write!(f, "no-coupon"),
+            Action::ShipTo(region) => write!(f, "ship {}", region),
+            Action::ChooseShipping(option) => write!(f, "shipping {}", option),
+            Action::ChoosePayment(method) => write!(f, "payment {}", method),
+            Action::CancelCheckout => write!(f, "cancel"),
+            Action::FinalisePayment => write!(f, "pay"),
+            Action::Leave => write!(f, "leave"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseActionError {
+    UnknownCommand(String),
+    UnknownProduct(Sku),
+}
+
+impl fmt::Display for ParseActionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseActionError::UnknownCommand(input) => write!(f, "unknown command `{}`", input),
+            ParseActionError::UnknownProduct(sku) => write!(f, "no product with SKU `{}`", sku),
+        }
+    }
+}
+
+impl Error for ParseActionError {}
+
+impl Action {
+    // Parses the format `Display` writes. Products are referred to by SKU, so
+    // they're looked up in the catalogue.
+    pub fn parse(input: &str, catalogue: &Catalogue) -> Result<Action, ParseActionError> {
+        let unknown = || ParseActionError::UnknownCommand(input.trim().to_string());
+        let product = |sku: &str| {
+            let sku = Sku::from(sku);
+            catalogue
+                .get(&sku)
+                .cloned()
+                .ok_or(ParseActionError::UnknownProduct(sku))
+        };
+
+        let words: Vec<&str> = input.split_whitespace().collect();
+        let action = match words.as_slice() {
+            ["visit"] => Action::VisitSite,
+            ["add", sku] => Action::AddItem(product(sku)?),
+            ["pop"] => Action::PopItem,
+            ["remove", sku] => Action::RemoveItem(Sku::from(*sku)),
+            ["set", sku, quantity] => {
+                Action::SetQuantity(Sku::from(*sku), quantity.parse().map_err(|_| unknown())?)
+            }
+            ["clear"] => Action::ClearCart,
+            ["checkout"] => Action::ProceedToCheckout,
+            ["coupon", code] => Action::ApplyCoupon(code.to_string()),
+            ["no-coupon"] => Action::RemoveCoupon,
+            ["ship", region] => Action::ShipTo(Region::from(*region)),
+            ["shipping", option] => Action::ChooseShipping(option.parse().map_err(|_| unknown())?),
+            ["payment", method] => Action::ChoosePayment(method.parse().map_err(|_| unknown())?),
+            ["cancel"] => Action::CancelCheckout,
+            ["pay"] => Action::FinalisePayment,
+            ["leave"] => Action::Leave,
+            _ => return Err(unknown()),
+        };
+        Ok(action)
+    }
+}
+
+// Returned when an action isn't one of the transitions of the customer's
+// current state, e.g. `PopItem` while "Browsing".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidTransition {
+    pub from: &'static str,
+    pub action: Action,
+}
+
+impl fmt::Display for InvalidTransition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "can't {} while {}", self.action.method(), self.from)
+    }
+}
+
+impl Error for InvalidTransition {}
+
+// Why a transition that exists for the customer's state refused to happen,
+// e.g. a coupon that can't be used on the cart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rejection {
+    Coupon(CouponError),
+    Tax(TaxError),
+    OutOfStock(OutOfStock),
+    Payment(PaymentError),
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::Coupon(err) => write!(f, "{}", err),
+            Rejection::Tax(err) => write!(f, "{}", err),
+            Rejection::OutOfStock(err) => write!(f, "{}", err),
+            Rejection::Payment(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for Rejection {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Rejection::Coupon(err) => Some(err),
+            Rejection::Tax(err) => Some(err),
+            Rejection::OutOfStock(err) => Some(err),
+            Rejection::Payment(err) => Some(err),
+        }
+    }
+}
+
+pub enum ApplyError {
+    // The action isn't a transition of the customer's state, so the customer
+    // is handed back untouched.
+    Invalid {
+        customer: AnyCustomer,
+        err: InvalidTransition,
+    },
+    // The transition refused to happen, and handed the customer back as it
+    // was.
+    Rejected {
+        customer: AnyCustomer,
+        reason: Rejection,
+    },
+}
+
+// Written out as `AnyCustomer` isn't `Debug`, so only its state is shown.
+impl fmt::Debug for ApplyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApplyError::Invalid { customer, err } => f
+                .debug_struct("Invalid")
+                .field("state", &customer.state_name())
+                .field("err", err)
+                .finish(),
+            ApplyError::Rejected { customer, reason } => f
+                .debug_struct("Rejected")
+                .field("state", &customer.state_name())
+                .field("reason", reason)
+                .finish(),
+        }
+    }
+}
+
+impl fmt::Display for ApplyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApplyError::Invalid { err, .. } => write!(f, "{}", err),
+            ApplyError::Rejected { reason, .. } => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl Error for ApplyError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ApplyError::Invalid { err, .. } => Some(err),
+            ApplyError::Rejected { reason, .. } => Some(reason),
+        }
+    }
+}
+
+impl AnyCustomer {
+    // Whether `apply()` would accept the action in the current state.
+    pub fn allows(&self, action: &Action) -> bool {
+        self.transitions()
+            .iter()
+            .any(|transition| transition.method == action.method())
+    }
+
+    // Routes the action to the matching method of the typed customer, so the
+    // `impl Customer<...>` blocks stay the only place where transitions are
+    // defined. Like the typed transitions that can fail, an invalid or rejected
+    // action hands the customer back as it was, reservations and all.
+    pub fn apply(self, action: Action) -> Result<AnyCustomer, ApplyError> {
+        let from = self.state_name();
+        let next = match (self, action) {
+            (AnyCustomer::Browsing(customer), Action::AddItem(product)) => {
+                customer.add_item(product).into()
+            }
+            (AnyCustomer::Browsing(customer), Action::Leave) => AnyCustomer::Left(customer.leave()),
+            (AnyCustomer::Shopping(customer), Action::AddItem(product)) => {
+                customer.add_item(product).into()
+            }
+            (AnyCustomer::Shopping(customer), Action::PopItem) => customer.pop_item().into(),
+            (AnyCustomer::Shopping(customer), Action::RemoveItem(sku)) => {
+                customer.remove_item(&sku).into()
+            }
+            (AnyCustomer::Shopping(customer), Action::SetQuantity(sku, quantity)) => {
+                customer.set_quantity(&sku, quantity).into()
+            }
+            (AnyCustomer::Shopping(customer), Action::ClearCart) => customer.clear_cart().into(),
+            (AnyCustomer::Shopping(customer), Action::ProceedToCheckout) => {
+                match customer.proceed_to_checkout() {
+                    Ok(customer) => customer.into(),
+                    Err((customer, err)) => {
+                        return Err(ApplyError::Rejected {
+                            customer: customer.into(),
+                            reason: Rejection::OutOfStock(err),
+                        })
+                    }
+                }
+            }
+            (AnyCustomer::NeedsAddress(customer), Action::ApplyCoupon(code)) => {
+                apply_coupon(customer, &code)?
+            }
+            (AnyCustomer::NeedsAddress(customer), Action::RemoveCoupon) => {
+                customer.remove_coupon().into()
+            }
+            (AnyCustomer::NeedsAddress(customer), Action::CancelCheckout) => {
+                customer.cancel_checkout().into()
+            }
+            (AnyCustomer::NeedsShipping(customer), Action::ApplyCoupon(code)) => {
+                apply_coupon(customer, &code)?
+            }
+            (AnyCustomer::NeedsShipping(customer), Action::RemoveCoupon) => {
+                customer.remove_coupon().into()
+            }
+            (AnyCustomer::NeedsShipping(customer), Action::CancelCheckout) => {
+                customer.cancel_checkout().into()
+            }
+            (AnyCustomer::NeedsPayment(customer), Action::ApplyCoupon(code)) => {
+                apply_coupon(customer, &code)?
+            }
+            (AnyCustomer::NeedsPayment(customer), Action::RemoveCoupon) => {
+                customer.remove_coupon().into()
+            }
+            (AnyCustomer::NeedsPayment(customer), Action::CancelCheckout) => {
+                customer.cancel_checkout().into()
+            }
+            (AnyCustomer::ReadyToPay(customer), Action::ApplyCoupon(code)) => {
+                apply_coupon(customer, &code)?
+            }
+            (AnyCustomer::ReadyToPay(customer), Action::RemoveCoupon) => {
+                customer.remove_coupon().into()
+            }
+            (AnyCustomer::ReadyToPay(customer), Action::CancelCheckout) => {
+                customer.cancel_checkout().into()
+            }
+            (AnyCustomer::NeedsAddress(customer), Action::ShipTo(region)) => {
+                match customer.ship_to(region) {
+                    Ok(customer) => customer.into(),
+                    Err((customer, err)) => {
+                        return Err(ApplyError::Rejected {
+                            customer: customer.into(),
+                            reason: Rejection::Tax(err),
+                        })
+                    }
+                }
+            }
+            (AnyCustomer::NeedsShipping(customer), Action::ChooseShipping(option)) => {
+                customer.choose_shipping(option).into()
+            }
+            (AnyCustomer::NeedsPayment(customer), Action::ChoosePayment(method)) => {
+                customer.choose_payment(method).into()
+            }
+            (AnyCustomer::ReadyToPay(customer), Action::ChoosePayment(method)) => {
+                customer.choose_payment(method).into()
+            }
+            (AnyCustomer::ReadyToPay(customer), Action::FinalisePayment) => {
+                let gateway = customer.shop.payments.clone();
+                match customer.finalise_payment(&*gateway) {
+                    Ok(order) => AnyCustomer::Paid(order),
+                    Err((customer, err)) => {
+                        return Err(ApplyError::Rejected {
+                            customer: customer.into(),
+                            reason: Rejection::Payment(err),
+                        })
+                    }
+                }
+            }
+            (customer, action) => {
+                return Err(ApplyError::Invalid {
+                    customer,
+                    err: InvalidTransition { from, action },
+                })
+            }
+        };
+        Ok(next)
+    }
+}
+
+// Coupons can be applied at every step of checking out, which all hand the
+// customer back the same way if the coupon can't be used.
+fn apply_coupon<S: CheckoutStep>(
+    customer: Customer<Checkout<S>>,
+    code: &str,
+) -> Result<AnyCustomer, ApplyError>
+where
+    Customer<Checkout<S>>: Into<AnyCustomer>,
+{
+    match customer.apply_coupon(code) {
+        Ok(customer) => Ok(customer.into()),
+        Err((customer, err)) => Err(ApplyError::Rejected {
+            customer: customer.into(),
+            reason: Rejection::Coupon(err),
+        }),
+    }
+}
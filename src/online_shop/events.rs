@@ -0,0 +1,116 @@
+use std::sync::{Arc, Mutex};
+
+use super::{cart, display_cart, Action, LineItem, Sku};
+
+// A structured record of a single transition, handed to the customer's sink.
+// `from` is `None` for `visit_site()`, as there's no state before entering the
+// flow, and `to` is "Left" or "Paid" once the flow has been exited.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransitionEvent {
+    pub from: Option<&'static str>,
+    pub to: &'static str,
+    pub action: Action,
+    pub cart_before: Vec<LineItem>,
+    pub cart_after: Vec<LineItem>,
+}
+
+// Receives every transition a customer goes through. The library itself never
+// prints anything, it's up to the sink to decide what to do with the events.
+// Sinks are `Send` so customers can still be moved across threads.
+pub trait EventSink: Send {
+    fn record(&mut self, _event: &TransitionEvent) {}
+}
+
+// Ignores everything, and is what `Customer::visit_site()` uses.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopSink;
+
+impl EventSink for NoopSink {}
+
+// Prints the same messages the transitions used to print themselves.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdoutSink;
+
+impl EventSink for StdoutSink {
+    fn record(&mut self, event: &TransitionEvent) {
+        match &event.action {
+            Action::VisitSite => println!("Hi site!"),
+            Action::Leave => println!("Not buying anything, bye site!"),
+            Action::AddItem(product) => println!(
+                "Added {} to cart {}",
+                product.name,
+                display_cart(&event.cart_after)
+            ),
+            Action::PopItem => {
+                if let Some(popped) = event.cart_before.last() {
+                    println!(
+                        "Removed {} from cart {}",
+                        popped.product.name,
+                        display_cart(&event.cart_after)
+                    );
+                }
+            }
+            Action::RemoveItem(sku) | Action::SetQuantity(sku, 0) => {
+                // Removing something that isn't in the cart is a no-op, and so
+                // is its message.
+                if let Some(removed) = find(&event.cart_before, sku) {
+                    println!(
+                        "Removed every {} from cart {}",
+                        removed.product.name,
+                        display_cart(&event.cart_after)
+                    );
+                }
+            }
+            Action::SetQuantity(sku, quantity) => {
+                if let Some(line) = find(&event.cart_before, sku) {
+                    println!(
+                        "Now {} x {} in cart {}",
+                        quantity,
+                        line.product.name,
+                        display_cart(&event.cart_after)
+                    );
+                }
+            }
+            Action::ClearCart => println!("Cart has been cleared."),
+            Action::ProceedToCheckout => match cart::subtotal(&event.cart_after) {
+                Ok(subtotal) => println!("Proceeding to checkout, the subtotal is {}.", subtotal),
+                Err(_) => println!("Proceeding to checkout."),
+            },
+            Action::ApplyCoupon(code) => println!("Applied coupon {}.", code),
+            Action::RemoveCoupon => println!("Removed the coupon."),
+            Action::ShipTo(region) => println!("Shipping to {}.", region),
+            Action::ChooseShipping(option) => println!("Going with {} shipping.", option),
+            Action::ChoosePayment(method) => println!("Paying by {}.", method),
+            Action::CancelCheckout => println!("Cancelling checkout, continue shopping."),
+            Action::FinalisePayment => println!("Done paying for the items, bye site!"),
+        }
+    }
+}
+
+fn find<'a>(cart: &'a [LineItem], sku: &Sku) -> Option<&'a LineItem> {
+    cart.iter().find(|line| line.product.sku == *sku)
+}
+
+// Collects the events so they can be inspected afterwards, e.g. in tests. The
+// customer takes ownership of its sink, so keep a clone of this around: all
+// clones share the same events.
+#[derive(Debug, Clone, Default)]
+pub struct VecSink {
+    events: Arc<Mutex<Vec<TransitionEvent>>>,
+}
+
+impl VecSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn events(&self) -> Vec<TransitionEvent> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+impl EventSink for VecSink {
+    fn record(&mut self, event: &TransitionEvent) {
+        self.events.lock().unwrap().push(event.clone());
+    }
+}
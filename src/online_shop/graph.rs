@@ -0,0 +1,208 @@
+use std::fmt::Write;
+
+use super::{
+    Browsing, Cancelled, Checkout, CustomerState, Delivered, NeedsAddress, NeedsPayment,
+    NeedsShipping, OrderState, Packed, Paid, ReadyToPay, Refunded, ReturnReceived, ReturnRejected,
+    ReturnRequested, Shipped, Shopping, Transition,
+};
+
+// A state of the flow. The customer's terminal ones ("Left" and "Paid") aren't
+// customer states, they're only reached by the transitions that consume the
+// customer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Node {
+    pub name: &'static str,
+    pub is_terminal: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub from: &'static str,
+    pub method: &'static str,
+    pub to: &'static str,
+}
+
+// The whole state machine, as described by the state markers themselves. The
+// flow is entered into `initial` via `entry`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Graph {
+    pub name: &'static str,
+    pub entry: &'static str,
+    pub initial: &'static str,
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+// A state marker's name, whether it's terminal, and its transitions.
+type State = (&'static str, bool, &'static [Transition]);
+
+// Builds the graph from the `CustomerState` impls, so it can't drift away from
+// the code the way a hand-drawn diagram does.
+pub fn graph() -> Graph {
+    build(
+        "Customer",
+        "visit_site",
+        Browsing::NAME,
+        &[
+            state::<Browsing>(),
+            state::<Shopping>(),
+            state::<Checkout<NeedsAddress>>(),
+            state::<Checkout<NeedsShipping>>(),
+            state::<Checkout<NeedsPayment>>(),
+            state::<Checkout<ReadyToPay>>(),
+        ],
+    )
+}
+
+fn state<S: CustomerState>() -> State {
+    (S::NAME, S::IS_TERMINAL, S::TRANSITIONS)
+}
+
+// The same for the `OrderState` impls, picking up where the customer's flow
+// leaves off.
+pub fn order_graph() -> Graph {
+    build(
+        "Order",
+        "finalise_payment",
+        Paid::NAME,
+        &[
+            (Paid::NAME, Paid::IS_TERMINAL, Paid::TRANSITIONS),
+            (Packed::NAME, Packed::IS_TERMINAL, Packed::TRANSITIONS),
+            (Shipped::NAME, Shipped::IS_TERMINAL, Shipped::TRANSITIONS),
+            (
+                Delivered::NAME,
+                Delivered::IS_TERMINAL,
+                Delivered::TRANSITIONS,
+            ),
+            (
+                Cancelled::NAME,
+                Cancelled::IS_TERMINAL,
+                Cancelled::TRANSITIONS,
+            ),
+            (
+                ReturnRequested::NAME,
+                ReturnRequested::IS_TERMINAL,
+                ReturnRequested::TRANSITIONS,
+            ),
+            (
+                ReturnReceived::NAME,
+                ReturnReceived::IS_TERMINAL,
+                ReturnReceived::TRANSITIONS,
+            ),
+            (Refunded::NAME, Refunded::IS_TERMINAL, Refunded::TRANSITIONS),
+            (
+                ReturnRejected::NAME,
+                ReturnRejected::IS_TERMINAL,
+                ReturnRejected::TRANSITIONS,
+            ),
+        ],
+    )
+}
+
+fn build(
+    name: &'static str,
+    entry: &'static str,
+    initial: &'static str,
+    states: &[State],
+) -> Graph {
+    let mut nodes: Vec<Node> = states
+        .iter()
+        .map(|&(name, is_terminal, _)| Node { name, is_terminal })
+        .collect();
+    let mut edges = vec![];
+    for &(from, _, transitions) in states {
+        for transition in transitions {
+            edges.push(Edge {
+                from,
+                method: transition.method,
+                to: transition.to,
+            });
+        }
+    }
+
+    // Anything that's reachable but isn't a state marker is an exit.
+    for edge in &edges {
+        if !nodes.iter().any(|node| node.name == edge.to) {
+            nodes.push(Node {
+                name: edge.to,
+                is_terminal: true,
+            });
+        }
+    }
+
+    Graph {
+        name,
+        entry,
+        initial,
+        nodes,
+        edges,
+    }
+}
+
+impl Graph {
+    // Renders the graph in Graphviz's DOT language, e.g. for `dot -Tsvg`.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        writeln!(dot, "digraph {} {{", self.name).unwrap();
+        writeln!(dot, "    rankdir=LR;").unwrap();
+        writeln!(dot, "    start [shape=point];").unwrap();
+        for node in &self.nodes {
+            let shape = if node.is_terminal {
+                "doublecircle"
+            } else {
+                "circle"
+            };
+            writeln!(dot, "    {} [shape={}];", node.name, shape).unwrap();
+        }
+        writeln!(
+            dot,
+            "    start -> {} [label=\"{}\"];",
+            self.initial, self.entry
+        )
+        .unwrap();
+        for edge in &self.edges {
+            writeln!(
+                dot,
+                "    {} -> {} [label=\"{}\"];",
+                edge.from, edge.to, edge.method
+            )
+            .unwrap();
+        }
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+
+    // Renders the graph as a Mermaid `stateDiagram-v2`.
+    pub fn to_mermaid(&self) -> String {
+        let mut mermaid = String::new();
+        writeln!(mermaid, "stateDiagram-v2").unwrap();
+        writeln!(mermaid, "    [*] --> {}: {}", self.initial, self.entry).unwrap();
+        for edge in &self.edges {
+            writeln!(
+                mermaid,
+                "    {} --> {}: {}",
+                edge.from, edge.to, edge.method
+            )
+            .unwrap();
+        }
+        for node in self.nodes.iter().filter(|node| node.is_terminal) {
+            writeln!(mermaid, "    {} --> [*]", node.name).unwrap();
+        }
+        mermaid
+    }
+
+    // Renders the graph as a PlantUML state diagram.
+    pub fn to_plantuml(&self) -> String {
+        let mut plantuml = String::new();
+        writeln!(plantuml, "@startuml").unwrap();
+        writeln!(plantuml, "[*] --> {} : {}", self.initial, self.entry).unwrap();
+        for edge in &self.edges {
+            writeln!(plantuml, "{} --> {} : {}", edge.from, edge.to, edge.method).unwrap();
+        }
+        for node in self.nodes.iter().filter(|node| node.is_terminal) {
+            writeln!(plantuml, "{} --> [*]", node.name).unwrap();
+        }
+        writeln!(plantuml, "@enduml").unwrap();
+        plantuml
+    }
+}
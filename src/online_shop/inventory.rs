@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::{Clock, LineItem, Sku, SystemClock, Timestamp};
+
+// What stock is reserved under. Every visit to the shop gets its own, so the
+// same customer checking out in two places at once (or two guests sharing an
+// id) don't take each other's reservations.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ReservationId(String);
+
+impl ReservationId {
+    pub fn new(id: impl Into<String>) -> Self {
+        ReservationId(id.into())
+    }
+
+    // A fresh id, which is never the same as any other made this way.
+    pub fn unique() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        ReservationId(format!(
+            "reservation-{}",
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for ReservationId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&self.0)
+    }
+}
+
+// A product there isn't enough of to reserve what's in the cart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unavailable {
+    pub sku: Sku,
+    pub requested: u32,
+    pub available: u32,
+}
+
+// Every product of the cart that's short, in cart order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutOfStock(pub Vec<Unavailable>);
+
+impl fmt::Display for OutOfStock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "not enough in stock:")?;
+        for (i, item) in self.0.iter().enumerate() {
+            let separator = if i == 0 { " " } else { ", " };
+            write!(
+                f,
+                "{}{} ({} wanted, {} left)",
+                separator, item.sku, item.requested, item.available
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for OutOfStock {}
+
+// Keeps track of what's in stock. Stock is reserved for a visit while the
+// customer checks out, so nobody else can buy it from under them, and only taken
+// out of stock for good once they've paid.
+pub trait Inventory: Send + Sync {
+    // How many of the product can still be reserved, or `None` if its stock
+    // isn't tracked at all (so there's always enough).
+    fn available(&self, sku: &Sku) -> Option<u32>;
+
+    // Reserves every line of the cart, replacing whatever was reserved under
+    // the same id before. Either everything is reserved, or nothing is.
+    fn reserve(&self, reservation: &ReservationId, cart: &[LineItem]) -> Result<(), OutOfStock>;
+
+    // Gives back whatever is reserved under the id, if anything.
+    fn release(&self, reservation: &ReservationId);
+
+    // Takes the cart out of stock for good. This uses the reservation, but
+    // also works once it has expired as long as there's still enough in
+    // stock.
+    fn commit(&self, reservation: &ReservationId, cart: &[LineItem]) -> Result<(), OutOfStock>;
+
+    // Puts what was taken out of stock by `commit()` back, e.g. when an order
+    // is cancelled before it's shipped.
+    fn restock(&self, lines: &[LineItem]);
+}
+
+// An `Inventory` kept in memory, where reservations expire after a while so
+// abandoned checkouts don't lock stock away forever. Only products given stock
+// with `with_stock()` or `set_stock()` are tracked, anything else (e.g. made
+// to order) is always available.
+//
+// This is shared by every customer of a shop, so all clones refer to the same
+// stock.
+#[derive(Clone)]
+pub struct InMemoryInventory {
+    state: Arc<Mutex<Stock>>,
+    clock: Arc<dyn Clock>,
+    ttl: Duration,
+}
+
+#[derive(Default)]
+struct Stock {
+    on_hand: HashMap<Sku, u32>,
+    reservations: HashMap<ReservationId, Reservation>,
+}
+
+#[derive(Clone)]
+struct Reservation {
+    quantities: HashMap<Sku, u32>,
+    expires_at: Timestamp,
+}
+
+impl Stock {
+    fn expire(&mut self, now: Timestamp) {
+        self.reservations
+            .retain(|_, reservation| reservation.expires_at > now);
+    }
+
+    fn available(&self, sku: &Sku) -> Option<u32> {
+        let on_hand = *self.on_hand.get(sku)?;
+        let reserved: u32 = self
+            .reservations
+            .values()
+            .filter_map(|reservation| reservation.quantities.get(sku))
+            .sum();
+        Some(on_hand.saturating_sub(reserved))
+    }
+
+    // What's short of the quantities in the cart, in cart order.
+    fn shortfall(&self, cart: &[LineItem], quantities: &HashMap<Sku, u32>) -> Vec<Unavailable> {
+        let mut unavailable: Vec<Unavailable> = vec![];
+        for line in cart {
+            let sku = &line.product.sku;
+            if unavailable.iter().any(|item| item.sku == *sku) {
+                continue;
+            }
+            let requested = quantities[sku];
+            if let Some(available) = self.available(sku) {
+                if available < requested {
+                    unavailable.push(Unavailable {
+                        sku: sku.clone(),
+                        requested,
+                        available,
+                    });
+                }
+            }
+        }
+        unavailable
+    }
+}
+
+// How many of each product are in the cart.
+fn quantities(cart: &[LineItem]) -> HashMap<Sku, u32> {
+    let mut quantities = HashMap::new();
+    for line in cart {
+        let quantity = quantities.entry(line.product.sku.clone()).or_insert(0u32);
+        *quantity = quantity.saturating_add(line.quantity.get());
+    }
+    quantities
+}
+
+impl InMemoryInventory {
+    // Reservations last 15 minutes unless said otherwise.
+    pub const DEFAULT_TTL: Duration = Duration::from_secs(15 * 60);
+
+    pub fn new(clock: impl Clock + 'static) -> Self {
+        InMemoryInventory {
+            state: Arc::default(),
+            clock: Arc::new(clock),
+            ttl: Self::DEFAULT_TTL,
+        }
+    }
+
+    // How long a reservation lasts before its stock is available again.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn with_stock(self, sku: impl Into<Sku>, quantity: u32) -> Self {
+        self.set_stock(sku, quantity);
+        self
+    }
+
+    // Sets how many of the product are on hand, reserved or not.
+    pub fn set_stock(&self, sku: impl Into<Sku>, quantity: u32) {
+        let mut state = self.state.lock().unwrap();
+        state.on_hand.insert(sku.into(), quantity);
+    }
+
+    // How many of the product are on hand, including what's reserved.
+    pub fn on_hand(&self, sku: &Sku) -> Option<u32> {
+        self.state.lock().unwrap().on_hand.get(sku).copied()
+    }
+}
+
+impl Inventory for InMemoryInventory {
+    fn available(&self, sku: &Sku) -> Option<u32> {
+        let mut state = self.state.lock().unwrap();
+        state.expire(self.clock.now());
+        state.available(sku)
+    }
+
+    fn reserve(&self, reservation: &ReservationId, cart: &[LineItem]) -> Result<(), OutOfStock> {
+        let now = self.clock.now();
+        let mut state = self.state.lock().unwrap();
+        state.expire(now);
+
+        // What's already reserved under the id doesn't count against it.
+        let previous = state.reservations.remove(reservation);
+        let quantities = quantities(cart);
+        let unavailable = state.shortfall(cart, &quantities);
+        if !unavailable.is_empty() {
+            if let Some(previous) = previous {
+                state.reservations.insert(reservation.clone(), previous);
+            }
+            return Err(OutOfStock(unavailable));
+        }
+        state.reservations.insert(
+            reservation.clone(),
+            Reservation {
+                quantities,
+                expires_at: now + self.ttl,
+            },
+        );
+        Ok(())
+    }
+
+    fn release(&self, reservation: &ReservationId) {
+        self.state.lock().unwrap().reservations.remove(reservation);
+    }
+
+    fn commit(&self, reservation: &ReservationId, cart: &[LineItem]) -> Result<(), OutOfStock> {
+        let mut state = self.state.lock().unwrap();
+        state.expire(self.clock.now());
+
+        let previous = state.reservations.remove(reservation);
+        let quantities = quantities(cart);
+        let unavailable = state.shortfall(cart, &quantities);
+        if !unavailable.is_empty() {
+            if let Some(previous) = previous {
+                state.reservations.insert(reservation.clone(), previous);
+            }
+            return Err(OutOfStock(unavailable));
+        }
+        for (sku, quantity) in quantities {
+            if let Some(on_hand) = state.on_hand.get_mut(&sku) {
+                *on_hand -= quantity;
+            }
+        }
+        Ok(())
+    }
+
+    fn restock(&self, lines: &[LineItem]) {
+        let mut state = self.state.lock().unwrap();
+        for (sku, quantity) in quantities(lines) {
+            if let Some(on_hand) = state.on_hand.get_mut(&sku) {
+                *on_hand = on_hand.saturating_add(quantity);
+            }
+        }
+    }
+}
+
+// Nothing tracked, on the system clock.
+impl Default for InMemoryInventory {
+    fn default() -> Self {
+        InMemoryInventory::new(SystemClock)
+    }
+}
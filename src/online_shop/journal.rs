@@ -0,0 +1,175 @@
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    Action, AnyCustomer, ApplyError, Customer, CustomerId, EventSink, InvalidTransition, NoopSink,
+    Rejection, Shop, TransitionEvent,
+};
+
+// One entry of a customer's journal: what was done, and the state it led to.
+// Unlike `TransitionEvent`, this is serializable, so a journal can be persisted
+// and replayed later on (event sourcing, crash recovery etc.).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CustomerEvent {
+    pub action: Action,
+    pub state: String,
+}
+
+// An append-only log of every transition a customer goes through, starting
+// with `visit_site()`. Like `VecSink`, all clones share the same events.
+#[derive(Debug, Clone, Default)]
+pub struct Journal {
+    events: Arc<Mutex<Vec<CustomerEvent>>>,
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn events(&self) -> Vec<CustomerEvent> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+impl EventSink for Journal {
+    fn record(&mut self, event: &TransitionEvent) {
+        self.events.lock().unwrap().push(CustomerEvent {
+            action: event.action.clone(),
+            state: event.to.to_string(),
+        });
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    // The journal has to start by visiting the site, which puts the customer
+    // in "Browsing".
+    MissingVisit,
+    // The action isn't allowed in the state the customer was in at that point.
+    IllegalStep {
+        index: usize,
+        source: InvalidTransition,
+    },
+    // The action was allowed, but refused to happen, e.g. because the shop
+    // doesn't have the coupon the journal says was applied.
+    Rejected {
+        index: usize,
+        reason: Rejection,
+    },
+    // The journal says the customer paid. That isn't replayed, as it would
+    // charge them (and take the stock and use up the coupon) all over again.
+    AlreadyPaid {
+        index: usize,
+    },
+    // The action was allowed, but led somewhere other than what the journal
+    // says it did.
+    Diverged {
+        index: usize,
+        expected: String,
+        actual: &'static str,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::MissingVisit => write!(f, "journal doesn't start with visit_site"),
+            ReplayError::IllegalStep { index, source } => {
+                write!(f, "illegal step at event {}: {}", index, source)
+            }
+            ReplayError::Rejected { index, reason } => {
+                write!(f, "event {} was rejected: {}", index, reason)
+            }
+            ReplayError::AlreadyPaid { index } => {
+                write!(f, "event {} is a payment, which isn't taken again", index)
+            }
+            ReplayError::Diverged {
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "event {} led to {}, but the journal expected {}",
+                index, actual, expected
+            ),
+        }
+    }
+}
+
+impl Error for ReplayError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReplayError::IllegalStep { source, .. } => Some(source),
+            ReplayError::Rejected { reason, .. } => Some(reason),
+            _ => None,
+        }
+    }
+}
+
+// Rebuilds a customer by re-running the real transitions of the journal, so a
+// journal containing an illegal step (e.g. `PopItem` at checkout) is rejected
+// rather than trusted. Paying is the one step that's never re-run, so a journal
+// can only be replayed up to the point the customer was ready to pay.
+pub fn replay(events: &[CustomerEvent]) -> Result<AnyCustomer, ReplayError> {
+    replay_with(events, NoopSink)
+}
+
+// Same as `replay()`, but the transitions are reported to the given sink as
+// they're re-run. Replaying into a fresh `Journal` gives back the same events.
+pub fn replay_with(
+    events: &[CustomerEvent],
+    sink: impl EventSink + 'static,
+) -> Result<AnyCustomer, ReplayError> {
+    replay_in(&Shop::default(), CustomerId::guest(), events, sink)
+}
+
+// Same as `replay_with()`, but for a known customer of the given shop, which
+// is needed for a journal that depends on the shop's promotions.
+pub fn replay_in(
+    shop: &Shop,
+    id: CustomerId,
+    events: &[CustomerEvent],
+    sink: impl EventSink + 'static,
+) -> Result<AnyCustomer, ReplayError> {
+    let (first, rest) = events.split_first().ok_or(ReplayError::MissingVisit)?;
+    if first.action != Action::VisitSite {
+        return Err(ReplayError::MissingVisit);
+    }
+
+    let mut customer = AnyCustomer::from(Customer::visit_shop(shop, id, sink));
+    check_state(0, first, &customer)?;
+
+    for (index, event) in rest.iter().enumerate().map(|(i, event)| (i + 1, event)) {
+        if event.action == Action::FinalisePayment {
+            return Err(ReplayError::AlreadyPaid { index });
+        }
+        customer = customer
+            .apply(event.action.clone())
+            .map_err(|err| match err {
+                ApplyError::Invalid { err, .. } => ReplayError::IllegalStep { index, source: err },
+                ApplyError::Rejected { reason, .. } => ReplayError::Rejected { index, reason },
+            })?;
+        check_state(index, event, &customer)?;
+    }
+    Ok(customer)
+}
+
+fn check_state(
+    index: usize,
+    event: &CustomerEvent,
+    customer: &AnyCustomer,
+) -> Result<(), ReplayError> {
+    if event.state == customer.state_name() {
+        Ok(())
+    } else {
+        Err(ReplayError::Diverged {
+            index,
+            expected: event.state.clone(),
+            actual: customer.state_name(),
+        })
+    }
+}
@@ -0,0 +1,441 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+// The currencies money can be in, as marker types like the states of
+// `Customer`, so that amounts in different currencies are different types and
+// can't be mixed up. The trait is sealed like `CustomerState`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Eur;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Gbp;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Jpy;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Usd;
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for super::Eur {}
+    impl Sealed for super::Gbp {}
+    impl Sealed for super::Jpy {}
+    impl Sealed for super::Usd {}
+}
+
+pub trait Currency: sealed::Sealed + 'static {
+    // The ISO 4217 code.
+    const CODE: &'static str;
+    // How many digits there are after the decimal point, e.g. 2 for cents.
+    const DECIMALS: u32;
+}
+
+impl Currency for Eur {
+    const CODE: &'static str = "EUR";
+    const DECIMALS: u32 = 2;
+}
+
+impl Currency for Gbp {
+    const CODE: &'static str = "GBP";
+    const DECIMALS: u32 = 2;
+}
+
+impl Currency for Jpy {
+    const CODE: &'static str = "JPY";
+    const DECIMALS: u32 = 0;
+}
+
+impl Currency for Usd {
+    const CODE: &'static str = "USD";
+    const DECIMALS: u32 = 2;
+}
+
+// How to get rid of the fraction of a minor unit that dividing leaves behind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    // Towards zero.
+    Down,
+    // Away from zero.
+    Up,
+    // To the nearest, with halves going away from zero.
+    HalfUp,
+    // To the nearest, with halves going to the even neighbour (banker's
+    // rounding).
+    HalfEven,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoneyError {
+    Overflow,
+    DivisionByZero,
+    // The exchange rates don't know how to convert between the two.
+    NoRate {
+        from: &'static str,
+        to: &'static str,
+    },
+}
+
+impl fmt::Display for MoneyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoneyError::Overflow => write!(f, "amount is too large"),
+            MoneyError::DivisionByZero => write!(f, "division by zero"),
+            MoneyError::NoRate { from, to } => {
+                write!(f, "no exchange rate from {} to {}", from, to)
+            }
+        }
+    }
+}
+
+impl Error for MoneyError {}
+
+// An exact amount of money in the currency `C`, stored as a whole number of
+// the currency's minor units (e.g. cents), so there's never any floating point
+// involved. `Money<Eur>` and `Money<Usd>` are different types, so they can't be
+// added up by accident, and the only way from one to the other is `convert()`.
+// None of the arithmetic wraps around on overflow.
+pub struct Money<C: Currency> {
+    minor: i64,
+    _currency: PhantomData<C>,
+}
+
+impl<C: Currency> Money<C> {
+    pub fn from_minor(minor: i64) -> Self {
+        Money {
+            minor,
+            _currency: PhantomData,
+        }
+    }
+
+    pub fn zero() -> Self {
+        Money::from_minor(0)
+    }
+
+    pub fn minor(&self) -> i64 {
+        self.minor
+    }
+
+    pub fn currency(&self) -> &'static str {
+        C::CODE
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.minor < 0
+    }
+
+    pub fn checked_add(self, other: Money<C>) -> Result<Money<C>, MoneyError> {
+        let minor = self
+            .minor
+            .checked_add(other.minor)
+            .ok_or(MoneyError::Overflow)?;
+        Ok(Money::from_minor(minor))
+    }
+
+    pub fn checked_sub(self, other: Money<C>) -> Result<Money<C>, MoneyError> {
+        let minor = self
+            .minor
+            .checked_sub(other.minor)
+            .ok_or(MoneyError::Overflow)?;
+        Ok(Money::from_minor(minor))
+    }
+
+    pub fn checked_mul(self, factor: i64) -> Result<Money<C>, MoneyError> {
+        let minor = self.minor.checked_mul(factor).ok_or(MoneyError::Overflow)?;
+        Ok(Money::from_minor(minor))
+    }
+
+    // Multiplies by `numerator / denominator`, rounding the result to a whole
+    // minor unit. This is how percentages are taken, e.g. 19% is `19 / 100`.
+    pub fn checked_mul_ratio(
+        self,
+        numerator: i64,
+        denominator: i64,
+        rounding: Rounding,
+    ) -> Result<Money<C>, MoneyError> {
+        if denominator == 0 {
+            return Err(MoneyError::DivisionByZero);
+        }
+        let dividend = i128::from(self.minor) * i128::from(numerator);
+        let minor = round_div(dividend, i128::from(denominator), rounding);
+        let minor = i64::try_from(minor).map_err(|_| MoneyError::Overflow)?;
+        Ok(Money::from_minor(minor))
+    }
+
+    pub fn sum(amounts: impl IntoIterator<Item = Money<C>>) -> Result<Money<C>, MoneyError> {
+        amounts
+            .into_iter()
+            .try_fold(Money::zero(), Money::checked_add)
+    }
+
+    // The same amount in another currency, at the rate given by `rates`.
+    pub fn convert<T: Currency>(
+        self,
+        rates: &impl ExchangeRates,
+        rounding: Rounding,
+    ) -> Result<Money<T>, MoneyError> {
+        let rate = if C::CODE == T::CODE {
+            ExchangeRate::new(1, 1)
+        } else {
+            rates.rate(C::CODE, T::CODE).ok_or(MoneyError::NoRate {
+                from: C::CODE,
+                to: T::CODE,
+            })?
+        };
+        // The rate is between whole units, so it has to be scaled when the
+        // currencies don't have the same number of minor units.
+        let (mut numerator, mut denominator) =
+            (i128::from(rate.numerator), i128::from(rate.denominator));
+        if T::DECIMALS >= C::DECIMALS {
+            numerator *= 10i128.pow(T::DECIMALS - C::DECIMALS);
+        } else {
+            denominator *= 10i128.pow(C::DECIMALS - T::DECIMALS);
+        }
+        let dividend = i128::from(self.minor)
+            .checked_mul(numerator)
+            .ok_or(MoneyError::Overflow)?;
+        let minor = round_div(dividend, denominator, rounding);
+        let minor = i64::try_from(minor).map_err(|_| MoneyError::Overflow)?;
+        Ok(Money::from_minor(minor))
+    }
+}
+
+// These are written out rather than derived, so that code generic over
+// `C: Currency` can use them without bounding `C` any further.
+impl<C: Currency> Clone for Money<C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C: Currency> Copy for Money<C> {}
+
+impl<C: Currency> PartialEq for Money<C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.minor == other.minor
+    }
+}
+
+impl<C: Currency> Eq for Money<C> {}
+
+impl<C: Currency> PartialOrd for Money<C> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<C: Currency> Ord for Money<C> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.minor.cmp(&other.minor)
+    }
+}
+
+impl<C: Currency> Hash for Money<C> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.minor.hash(state);
+    }
+}
+
+impl<C: Currency> fmt::Debug for Money<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Money<{}>({})", C::CODE, self.minor)
+    }
+}
+
+// Serialized along with the currency code, which is checked when
+// deserializing so an amount can't silently change currency on the way.
+#[derive(Serialize, Deserialize)]
+struct MoneyRepr {
+    minor: i64,
+    currency: String,
+}
+
+impl<C: Currency> Serialize for Money<C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MoneyRepr {
+            minor: self.minor,
+            currency: C::CODE.to_string(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, C: Currency> Deserialize<'de> for Money<C> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = MoneyRepr::deserialize(deserializer)?;
+        if repr.currency != C::CODE {
+            return Err(de::Error::custom(format!(
+                "expected an amount in {}, found {}",
+                C::CODE,
+                repr.currency
+            )));
+        }
+        Ok(Money::from_minor(repr.minor))
+    }
+}
+
+// How many units of one currency one unit of another is worth, as an exact
+// fraction, e.g. `ExchangeRate::new(10853, 10000)` for 1.0853.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExchangeRate {
+    numerator: i64,
+    denominator: i64,
+}
+
+impl ExchangeRate {
+    // Panics if `denominator` isn't positive, as that's never a valid rate.
+    pub fn new(numerator: i64, denominator: i64) -> Self {
+        assert!(
+            denominator > 0,
+            "exchange rate denominators must be positive"
+        );
+        ExchangeRate {
+            numerator,
+            denominator,
+        }
+    }
+}
+
+// Where exchange rates come from. `Money::convert()` only ever asks for rates
+// between two different currencies.
+pub trait ExchangeRates {
+    fn rate(&self, from: &'static str, to: &'static str) -> Option<ExchangeRate>;
+}
+
+// A fixed set of rates, e.g. for tests or a daily snapshot.
+#[derive(Debug, Clone, Default)]
+pub struct FixedRates {
+    rates: HashMap<(&'static str, &'static str), ExchangeRate>,
+}
+
+impl FixedRates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_rate<F: Currency, T: Currency>(mut self, rate: ExchangeRate) -> Self {
+        self.rates.insert((F::CODE, T::CODE), rate);
+        self
+    }
+}
+
+impl ExchangeRates for FixedRates {
+    fn rate(&self, from: &'static str, to: &'static str) -> Option<ExchangeRate> {
+        self.rates.get(&(from, to)).copied()
+    }
+}
+
+fn round_div(dividend: i128, divisor: i128, rounding: Rounding) -> i128 {
+    let quotient = dividend / divisor;
+    let remainder = dividend % divisor;
+    if remainder == 0 {
+        return quotient;
+    }
+    // Which way is away from zero, and whether the remainder is more, less or
+    // exactly half of the divisor.
+    let away = if (dividend < 0) == (divisor < 0) {
+        1
+    } else {
+        -1
+    };
+    let twice = (remainder * 2).abs();
+    let round_away = match rounding {
+        Rounding::Down => false,
+        Rounding::Up => true,
+        Rounding::HalfUp => twice >= divisor.abs(),
+        Rounding::HalfEven => match twice.cmp(&divisor.abs()) {
+            Ordering::Less => false,
+            Ordering::Greater => true,
+            Ordering::Equal => quotient % 2 != 0,
+        },
+    };
+    if round_away {
+        quotient + away
+    } else {
+        quotient
+    }
+}
+
+// e.g. "12.50 EUR", or "1200 JPY" for currencies without minor units.
+impl<C: Currency> fmt::Display for Money<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.is_negative() { "-" } else { "" };
+        let decimals = C::DECIMALS;
+        let scale = 10u64.pow(decimals);
+        let minor = self.minor.unsigned_abs();
+        let amount = if decimals == 0 {
+            format!("{}{}", sign, minor)
+        } else {
+            format!(
+                "{}{}.{:0width$}",
+                sign,
+                minor / scale,
+                minor % scale,
+                width = decimals as usize
+            )
+        };
+        f.pad(&format!("{} {}", amount, C::CODE))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseMoneyError {
+    pub input: String,
+    pub currency: &'static str,
+}
+
+impl fmt::Display for ParseMoneyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` isn't an amount in {}", self.input, self.currency)
+    }
+}
+
+impl Error for ParseMoneyError {}
+
+// Reads back what `Display` writes, where the currency code can be left out.
+impl<C: Currency> FromStr for Money<C> {
+    type Err = ParseMoneyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseMoneyError {
+            input: s.trim().to_string(),
+            currency: C::CODE,
+        };
+        let amount = match s.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [amount] => *amount,
+            [amount, code] if *code == C::CODE => *amount,
+            _ => return Err(invalid()),
+        };
+        let (negative, amount) = match amount.strip_prefix('-') {
+            Some(amount) => (true, amount),
+            None => (false, amount),
+        };
+        let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+        let digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+        if whole.is_empty() || !digits(whole) || !digits(fraction) {
+            return Err(invalid());
+        }
+        if fraction.len() > C::DECIMALS as usize || (amount.contains('.') && fraction.is_empty()) {
+            return Err(invalid());
+        }
+
+        let scale = 10i64.pow(C::DECIMALS);
+        let fraction_scale = 10i64.pow(C::DECIMALS - fraction.len() as u32);
+        let whole: i64 = whole.parse().map_err(|_| invalid())?;
+        let fraction: i64 = if fraction.is_empty() {
+            0
+        } else {
+            fraction.parse().map_err(|_| invalid())?
+        };
+        let minor = whole
+            .checked_mul(scale)
+            .and_then(|minor| minor.checked_add(fraction * fraction_scale))
+            .ok_or_else(invalid)?;
+        Ok(Money::from_minor(if negative { -minor } else { minor }))
+    }
+}
@@ -0,0 +1,51 @@
+use std::ops::{Deref, DerefMut};
+
+// A `Vec` that always holds at least one item, which is how the cart is stored
+// in the "Shopping" and "Checkout" states: an empty checkout can't be written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NonEmpty<T> {
+    items: Vec<T>,
+}
+
+impl<T> NonEmpty<T> {
+    pub fn new(first: T) -> Self {
+        NonEmpty { items: vec![first] }
+    }
+
+    pub fn from_vec(items: Vec<T>) -> Option<Self> {
+        (!items.is_empty()).then_some(NonEmpty { items })
+    }
+
+    pub fn push(&mut self, item: T) {
+        self.items.push(item);
+    }
+
+    // Like `Vec::pop()`, except the last item is never removed, so this is
+    // `None` when there's only one left.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.items.len() > 1 {
+            self.items.pop()
+        } else {
+            None
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.items
+    }
+}
+
+impl<T> Deref for NonEmpty<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.items
+    }
+}
+
+// Only the items themselves can be changed through this, never the length.
+impl<T> DerefMut for NonEmpty<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        &mut self.items
+    }
+}
@@ -0,0 +1,429 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    CustomerId, Inventory, LineItem, Money, Payment, PaymentError, PaymentGateway, Pricing, Refund,
+    RefundLine, ReturnRequest, ShippingOption, StoreCurrency, TaxBreakdown, Timestamp, Transition,
+};
+
+// Identifies an order for good, e.g. when it's handed over to be fulfilled.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct OrderId(String);
+
+impl OrderId {
+    pub fn new(id: impl Into<String>) -> Self {
+        OrderId(id.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for OrderId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&self.0)
+    }
+}
+
+// The different states an order goes through once it's been paid for, which
+// is where the customer's flow leaves off. Unlike the customer's states, some
+// of these carry what's only known from that state on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Paid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Packed;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Shipped {
+    pub tracking: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Delivered {
+    pub delivered_at: Timestamp,
+}
+
+// `None` if nothing was paid, so there was nothing to refund.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cancelled {
+    pub refund: Option<Refund>,
+}
+
+// The states of a delivered order being returned, see `returns.rs`. They all
+// carry what the customer asked to return.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReturnRequested {
+    pub request: ReturnRequest,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReturnReceived {
+    pub request: ReturnRequest,
+}
+
+// `refund` is `None` if every line was refunded with nothing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Refunded {
+    pub request: ReturnRequest,
+    pub lines: Vec<RefundLine>,
+    pub refund: Option<Refund>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReturnRejected {
+    pub request: ReturnRequest,
+    pub reason: String,
+}
+
+// Sealed the same way as `CustomerState`, so `Order<String>` can't be named
+// and the states can't be added to from outside this crate.
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for super::Paid {}
+    impl Sealed for super::Packed {}
+    impl Sealed for super::Shipped {}
+    impl Sealed for super::Delivered {}
+    impl Sealed for super::Cancelled {}
+    impl Sealed for super::ReturnRequested {}
+    impl Sealed for super::ReturnReceived {}
+    impl Sealed for super::Refunded {}
+    impl Sealed for super::ReturnRejected {}
+}
+
+// Behaviour shared by every order state, like `CustomerState` is for the
+// customer's. Here the flow does end in states of its own, which are terminal:
+// they have no transitions, so an order in one of them stays there for good.
+pub trait OrderState: sealed::Sealed {
+    const NAME: &'static str;
+    const IS_TERMINAL: bool;
+    const TRANSITIONS: &'static [Transition];
+}
+
+impl OrderState for Paid {
+    const NAME: &'static str = "Paid";
+    const IS_TERMINAL: bool = false;
+    const TRANSITIONS: &'static [Transition] = &[
+        Transition {
+            method: "pack",
+            to: Packed::NAME,
+        },
+        Transition {
+            method: "cancel",
+            to: Cancelled::NAME,
+        },
+    ];
+}
+
+impl OrderState for Packed {
+    const NAME: &'static str = "Packed";
+    const IS_TERMINAL: bool = false;
+    const TRANSITIONS: &'static [Transition] = &[
+        Transition {
+            method: "ship",
+            to: Shipped::NAME,
+        },
+        Transition {
+            method: "cancel",
+            to: Cancelled::NAME,
+        },
+    ];
+}
+
+impl OrderState for Shipped {
+    const NAME: &'static str = "Shipped";
+    const IS_TERMINAL: bool = false;
+    const TRANSITIONS: &'static [Transition] = &[Transition {
+        method: "deliver",
+        to: Delivered::NAME,
+    }];
+}
+
+// Most orders stay here, but as they can still be returned for a while, it
+// isn't terminal.
+impl OrderState for Delivered {
+    const NAME: &'static str = "Delivered";
+    const IS_TERMINAL: bool = false;
+    const TRANSITIONS: &'static [Transition] = &[Transition {
+        method: "request_return",
+        to: ReturnRequested::NAME,
+    }];
+}
+
+impl OrderState for Cancelled {
+    const NAME: &'static str = "Cancelled";
+    const IS_TERMINAL: bool = true;
+    const TRANSITIONS: &'static [Transition] = &[];
+}
+
+impl OrderState for ReturnRequested {
+    const NAME: &'static str = "ReturnRequested";
+    const IS_TERMINAL: bool = false;
+    const TRANSITIONS: &'static [Transition] = &[Transition {
+        method: "receive_return",
+        to: ReturnReceived::NAME,
+    }];
+}
+
+impl OrderState for ReturnReceived {
+    const NAME: &'static str = "ReturnReceived";
+    const IS_TERMINAL: bool = false;
+    const TRANSITIONS: &'static [Transition] = &[
+        Transition {
+            method: "refund",
+            to: Refunded::NAME,
+        },
+        Transition {
+            method: "reject_return",
+            to: ReturnRejected::NAME,
+        },
+    ];
+}
+
+impl OrderState for Refunded {
+    const NAME: &'static str = "Refunded";
+    const IS_TERMINAL: bool = true;
+    const TRANSITIONS: &'static [Transition] = &[];
+}
+
+impl OrderState for ReturnRejected {
+    const NAME: &'static str = "ReturnRejected";
+    const IS_TERMINAL: bool = true;
+    const TRANSITIONS: &'static [Transition] = &[];
+}
+
+// What a customer ends up with once they've paid: what they bought, what it
+// cost them, and the payment covering it. Everything's copied out of the cart
+// as it was when paying, so later changes to the catalogue or the tax rules
+// don't change what was bought.
+//
+// Like `Customer`, the fields can't be set from outside the shop and the only
+// way to get an order is `Customer<Checkout<ReadyToPay>>::finalise_payment()`,
+// so an order can only be in a state it got to through the transitions below.
+// That's also why it can be serialized (e.g. to show or archive it) but not
+// deserialized, which would make e.g. a delivered order out of any JSON. It
+// isn't `Clone` either, so the same order can't be e.g. both shipped and
+// cancelled.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Order<S: OrderState> {
+    pub(super) id: OrderId,
+    pub(super) customer: CustomerId,
+    pub(super) placed_at: Timestamp,
+    pub(super) lines: Vec<LineItem>,
+    pub(super) coupon: Option<String>,
+    pub(super) subtotal: Money<StoreCurrency>,
+    pub(super) discount: Money<StoreCurrency>,
+    pub(super) tax: TaxBreakdown,
+    pub(super) total: Money<StoreCurrency>,
+    pub(super) shipping: ShippingOption,
+    pub(super) payment: Payment,
+    pub(super) state: S,
+}
+
+// Read-only accessors available in every state.
+impl<S: OrderState> Order<S> {
+    pub fn state_name(&self) -> &'static str {
+        S::NAME
+    }
+
+    // What's known about the order in its current state, e.g. the tracking
+    // number once it's shipped.
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    pub fn id(&self) -> &OrderId {
+        &self.id
+    }
+
+    pub fn customer(&self) -> &CustomerId {
+        &self.customer
+    }
+
+    pub fn placed_at(&self) -> Timestamp {
+        self.placed_at
+    }
+
+    pub fn lines(&self) -> &[LineItem] {
+        &self.lines
+    }
+
+    pub fn coupon(&self) -> Option<&str> {
+        self.coupon.as_deref()
+    }
+
+    // The sum of every line, at the prices in the catalogue.
+    pub fn subtotal(&self) -> Money<StoreCurrency> {
+        self.subtotal
+    }
+
+    pub fn discount(&self) -> Money<StoreCurrency> {
+        self.discount
+    }
+
+    // Which also says where the order is shipped to.
+    pub fn tax(&self) -> &TaxBreakdown {
+        &self.tax
+    }
+
+    // What was paid, which is also the amount of `payment()`.
+    pub fn total(&self) -> Money<StoreCurrency> {
+        self.total
+    }
+
+    pub fn shipping(&self) -> ShippingOption {
+        self.shipping
+    }
+
+    pub fn payment(&self) -> &Payment {
+        &self.payment
+    }
+
+    // Formats the order as a plain-text receipt, e.g.
+    //
+    //     Order order-1 for alice, placed 2024-03-01T09:30:00Z
+    //         2 x mug     Enamel mug               24.00 EUR
+    //         1 x tea     Loose leaf tea            4.50 EUR
+    //       Subtotal                               28.50 EUR
+    //       Discount (FIVEOFF)                     -5.00 EUR
+    //       Tax (NL, included)                      3.74 EUR
+    //       Total                                  23.50 EUR
+    //     Shipping standard to NL
+    //     Paid by card, reference pay-2
+    pub fn receipt(&self) -> impl fmt::Display + '_ {
+        struct Receipt<'a, S: OrderState>(&'a Order<S>);
+
+        impl<S: OrderState> fmt::Display for Receipt<'_, S> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let order = self.0;
+                writeln!(
+                    f,
+                    "Order {} for {}, placed {}",
+                    order.id, order.customer, order.placed_at
+                )?;
+                for line in &order.lines {
+                    let total = line.total().map_err(|_| fmt::Error)?;
+                    writeln!(
+                        f,
+                        "  {:>3} x {:<8}{:<20}{:>14}",
+                        line.quantity, line.product.sku, line.product.name, total
+                    )?;
+                }
+                writeln!(f, "  {:<34}{:>14}", "Subtotal", order.subtotal)?;
+                if let Some(code) = &order.coupon {
+                    let label = format!("Discount ({})", code);
+                    writeln!(f, "  {:<34}{:>14}", label, format!("-{}", order.discount))?;
+                }
+                let tax = &order.tax;
+                let included = tax
+                    .lines
+                    .iter()
+                    .all(|line| line.pricing == Pricing::Inclusive);
+                let label = if included {
+                    format!("Tax ({}, included)", tax.region)
+                } else {
+                    format!("Tax ({})", tax.region)
+                };
+                writeln!(f, "  {:<34}{:>14}", label, tax.tax)?;
+                writeln!(f, "  {:<34}{:>14}", "Total", order.total)?;
+                writeln!(f, "Shipping {} to {}", order.shipping, tax.region)?;
+                write!(
+                    f,
+                    "Paid by {}, reference {}",
+                    order.payment.method, order.payment.reference
+                )
+            }
+        }
+
+        Receipt(self)
+    }
+
+    // Moves every field over into an order of the next state, whose state is
+    // made from the current one. This isn't public, so the only way to change
+    // state from the outside is still through the transitions below (and in
+    // `returns.rs`).
+    pub(super) fn transition<T: OrderState>(self, next: impl FnOnce(S) -> T) -> Order<T> {
+        Order {
+            id: self.id,
+            customer: self.customer,
+            placed_at: self.placed_at,
+            lines: self.lines,
+            coupon: self.coupon,
+            subtotal: self.subtotal,
+            discount: self.discount,
+            tax: self.tax,
+            total: self.total,
+            shipping: self.shipping,
+            payment: self.payment,
+            state: next(self.state),
+        }
+    }
+
+    // Gives the customer back everything they paid and puts what they bought
+    // back in stock, handing the order back along with the reason if the
+    // refund doesn't go through.
+    fn refund_in_full(
+        self,
+        gateway: &dyn PaymentGateway,
+        inventory: &dyn Inventory,
+    ) -> Result<Order<Cancelled>, (Self, PaymentError)> {
+        let refund = if self.payment.amount == Money::zero() {
+            None
+        } else {
+            match gateway.refund(&self.payment, self.payment.amount) {
+                Ok(refund) => Some(refund),
+                Err(err) => return Err((self, err)),
+            }
+        };
+        inventory.restock(&self.lines);
+        Ok(self.transition(|_| Cancelled { refund }))
+    }
+}
+
+impl Order<Paid> {
+    // "Paid" -> "Packed"
+    pub fn pack(self) -> Order<Packed> {
+        self.transition(|Paid| Packed)
+    }
+
+    // "Paid" -> "Cancelled", refunding the payment in full and restocking
+    // the inventory it was taken from. If the refund doesn't go through, the
+    // order is handed back still paid for.
+    pub fn cancel(
+        self,
+        gateway: &dyn PaymentGateway,
+        inventory: &dyn Inventory,
+    ) -> Result<Order<Cancelled>, (Self, PaymentError)> {
+        self.refund_in_full(gateway, inventory)
+    }
+}
+
+impl Order<Packed> {
+    // "Packed" -> "Shipped"
+    pub fn ship(self, tracking: impl Into<String>) -> Order<Shipped> {
+        self.transition(|Packed| Shipped {
+            tracking: tracking.into(),
+        })
+    }
+
+    // "Packed" -> "Cancelled", the last chance to cancel before the parcel is
+    // on its way. There's no `cancel()` once shipped.
+    pub fn cancel(
+        self,
+        gateway: &dyn PaymentGateway,
+        inventory: &dyn Inventory,
+    ) -> Result<Order<Cancelled>, (Self, PaymentError)> {
+        self.refund_in_full(gateway, inventory)
+    }
+}
+
+impl Order<Shipped> {
+    // "Shipped" -> "Delivered", when the carrier says it was delivered.
+    pub fn deliver(self, delivered_at: Timestamp) -> Order<Delivered> {
+        self.transition(|_| Delivered { delivered_at })
+    }
+}
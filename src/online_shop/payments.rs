@@ -0,0 +1,395 @@
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use super::{CouponError, Money, MoneyError, OutOfStock, StoreCurrency, TaxError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PaymentMethod {
+    Card,
+    PayPal,
+    BankTransfer,
+}
+
+// As typed in by users of the `stated` binary, e.g. "card".
+impl fmt::Display for PaymentMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let method = match self {
+            PaymentMethod::Card => "card",
+            PaymentMethod::PayPal => "paypal",
+            PaymentMethod::BankTransfer => "bank-transfer",
+        };
+        f.pad(method)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsePaymentMethodError(pub String);
+
+impl fmt::Display for ParsePaymentMethodError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown payment method `{}`, try card, paypal or bank-transfer",
+            self.0
+        )
+    }
+}
+
+impl Error for ParsePaymentMethodError {}
+
+// Reads back what `Display` writes.
+impl FromStr for PaymentMethod {
+    type Err = ParsePaymentMethodError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "card" => Ok(PaymentMethod::Card),
+            "paypal" => Ok(PaymentMethod::PayPal),
+            "bank-transfer" => Ok(PaymentMethod::BankTransfer),
+            other => Err(ParsePaymentMethodError(other.to_string())),
+        }
+    }
+}
+
+// Money set aside on the customer's account, which isn't taken until it's
+// captured (or given back by voiding it).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Authorization {
+    pub id: String,
+    pub amount: Money<StoreCurrency>,
+    pub method: PaymentMethod,
+}
+
+// Money that's actually been taken, identified by the gateway's reference.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Payment {
+    pub reference: String,
+    pub amount: Money<StoreCurrency>,
+    pub method: PaymentMethod,
+}
+
+// Money given back from a payment, which may be less than was paid.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Refund {
+    pub reference: String,
+    pub payment: String,
+    pub amount: Money<StoreCurrency>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaymentError {
+    Declined(String),
+    // The gateway didn't answer in time. Nothing has happened as far as the
+    // shop can tell, so it's safe to try again.
+    TimedOut,
+    // The gateway is busy and asked for the request to be made again.
+    TryAgain,
+    // The gateway doesn't know about the authorization or payment, or it's
+    // already been captured or voided.
+    Unknown(String),
+    InvalidAmount(Money<StoreCurrency>),
+    RefundTooLarge {
+        reference: String,
+        requested: Money<StoreCurrency>,
+        refundable: Money<StoreCurrency>,
+    },
+    // Checking out doesn't get as far as asking the gateway.
+    OutOfStock(OutOfStock),
+    // The coupon doesn't apply anymore (e.g. it's expired since it was
+    // applied), so paying would cost more than the customer was shown.
+    Coupon(CouponError),
+    Tax(TaxError),
+    Money(MoneyError),
+    // The payment was taken, but the stock ran out before the order could be
+    // placed, and giving the money back failed too. The customer has been
+    // charged without getting an order, so the payment has to be refunded by
+    // other means.
+    NotRefunded {
+        payment: Payment,
+        out_of_stock: OutOfStock,
+        refund: Box<PaymentError>,
+    },
+}
+
+impl fmt::Display for PaymentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PaymentError::Declined(reason) => write!(f, "the payment was declined: {}", reason),
+            PaymentError::TimedOut => write!(f, "the payment gateway timed out"),
+            PaymentError::TryAgain => write!(f, "the payment gateway asked to try again"),
+            PaymentError::Unknown(reference) => {
+                write!(f, "the payment gateway doesn't know `{}`", reference)
+            }
+            PaymentError::InvalidAmount(amount) => write!(f, "can't pay or refund {}", amount),
+            PaymentError::RefundTooLarge {
+                reference,
+                requested,
+                refundable,
+            } => write!(
+                f,
+                "can't refund {} of `{}`, only {} is left to refund",
+                requested, reference, refundable
+            ),
+            PaymentError::OutOfStock(err) => write!(f, "{}", err),
+            PaymentError::Coupon(err) => write!(f, "{}", err),
+            PaymentError::Tax(err) => write!(f, "{}", err),
+            PaymentError::Money(err) => write!(f, "{}", err),
+            PaymentError::NotRefunded {
+                payment,
+                out_of_stock,
+                refund,
+            } => write!(
+                f,
+                "took {} as `{}` but {}, and refunding it failed: {}",
+                payment.amount, payment.reference, out_of_stock, refund
+            ),
+        }
+    }
+}
+
+impl Error for PaymentError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PaymentError::OutOfStock(err) => Some(err),
+            PaymentError::Coupon(err) => Some(err),
+            PaymentError::Tax(err) => Some(err),
+            PaymentError::Money(err) => Some(err),
+            PaymentError::NotRefunded { refund, .. } => Some(refund.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<TaxError> for PaymentError {
+    fn from(err: TaxError) -> Self {
+        PaymentError::Tax(err)
+    }
+}
+
+impl From<MoneyError> for PaymentError {
+    fn from(err: MoneyError) -> Self {
+        PaymentError::Money(err)
+    }
+}
+
+impl From<CouponError> for PaymentError {
+    fn from(err: CouponError) -> Self {
+        match err {
+            CouponError::Money(err) => PaymentError::Money(err),
+            err => PaymentError::Coupon(err),
+        }
+    }
+}
+
+impl From<OutOfStock> for PaymentError {
+    fn from(err: OutOfStock) -> Self {
+        PaymentError::OutOfStock(err)
+    }
+}
+
+// Takes the customer's money. Payments are made in two steps, authorizing
+// and then capturing, so that nothing is taken if something goes wrong in
+// between (in which case the authorization is voided).
+pub trait PaymentGateway: Send + Sync {
+    fn authorize(
+        &self,
+        amount: Money<StoreCurrency>,
+        method: PaymentMethod,
+    ) -> Result<Authorization, PaymentError>;
+
+    fn capture(&self, authorization: &Authorization) -> Result<Payment, PaymentError>;
+
+    fn void(&self, authorization: &Authorization) -> Result<(), PaymentError>;
+
+    // Gives back some or all of a payment, possibly over several refunds.
+    fn refund(
+        &self,
+        payment: &Payment,
+        amount: Money<StoreCurrency>,
+    ) -> Result<Refund, PaymentError>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Authorize,
+    Capture,
+    Void,
+    Refund,
+}
+
+// How the mock gateway answers instead of going through with an operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockResponse {
+    Decline(String),
+    TimeOut,
+    TryAgain,
+}
+
+// A `PaymentGateway` that keeps everything in memory and goes through with
+// every operation, unless it's been scripted to fail the next few. Ids are
+// numbered in order ("auth-1", "pay-1", "refund-1" ...), so runs are
+// repeatable.
+//
+// All clones share the same state, so keep one around to script failures
+// into the gateway handed to the shop.
+#[derive(Debug, Clone, Default)]
+pub struct MockGateway {
+    state: Arc<Mutex<MockState>>,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    script: HashMap<Operation, VecDeque<MockResponse>>,
+    calls: Vec<Operation>,
+    next_id: u64,
+    // The authorizations that can still be captured or voided.
+    open: HashMap<String, Authorization>,
+    // Every captured payment, along with how much of it has been refunded.
+    payments: HashMap<String, (Payment, Money<StoreCurrency>)>,
+}
+
+impl MockState {
+    // Logs the call, and fails it if that's what's next in the script.
+    fn call(&mut self, operation: Operation) -> Result<(), PaymentError> {
+        self.calls.push(operation);
+        let response = self
+            .script
+            .get_mut(&operation)
+            .and_then(VecDeque::pop_front);
+        match response {
+            None => Ok(()),
+            Some(MockResponse::Decline(reason)) => Err(PaymentError::Declined(reason)),
+            Some(MockResponse::TimeOut) => Err(PaymentError::TimedOut),
+            Some(MockResponse::TryAgain) => Err(PaymentError::TryAgain),
+        }
+    }
+
+    fn id(&mut self, prefix: &str) -> String {
+        self.next_id += 1;
+        format!("{}-{}", prefix, self.next_id)
+    }
+}
+
+impl MockGateway {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_script(self, operation: Operation, response: MockResponse) -> Self {
+        self.script(operation, response);
+        self
+    }
+
+    // Fails the next call of the operation that isn't failed by an earlier
+    // script already.
+    pub fn script(&self, operation: Operation, response: MockResponse) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .script
+            .entry(operation)
+            .or_default()
+            .push_back(response);
+    }
+
+    // Every call made so far, whether it went through or not.
+    pub fn calls(&self) -> Vec<Operation> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    // How much of the payment has been refunded, or `None` if there's no such
+    // payment.
+    pub fn refunded(&self, reference: &str) -> Option<Money<StoreCurrency>> {
+        let state = self.state.lock().unwrap();
+        state.payments.get(reference).map(|(_, refunded)| *refunded)
+    }
+}
+
+impl PaymentGateway for MockGateway {
+    fn authorize(
+        &self,
+        amount: Money<StoreCurrency>,
+        method: PaymentMethod,
+    ) -> Result<Authorization, PaymentError> {
+        let mut state = self.state.lock().unwrap();
+        state.call(Operation::Authorize)?;
+        if amount.is_negative() {
+            return Err(PaymentError::InvalidAmount(amount));
+        }
+        let authorization = Authorization {
+            id: state.id("auth"),
+            amount,
+            method,
+        };
+        state
+            .open
+            .insert(authorization.id.clone(), authorization.clone());
+        Ok(authorization)
+    }
+
+    fn capture(&self, authorization: &Authorization) -> Result<Payment, PaymentError> {
+        let mut state = self.state.lock().unwrap();
+        state.call(Operation::Capture)?;
+        let authorization = state
+            .open
+            .remove(&authorization.id)
+            .ok_or_else(|| PaymentError::Unknown(authorization.id.clone()))?;
+        let payment = Payment {
+            reference: state.id("pay"),
+            amount: authorization.amount,
+            method: authorization.method,
+        };
+        state
+            .payments
+            .insert(payment.reference.clone(), (payment.clone(), Money::zero()));
+        Ok(payment)
+    }
+
+    fn void(&self, authorization: &Authorization) -> Result<(), PaymentError> {
+        let mut state = self.state.lock().unwrap();
+        state.call(Operation::Void)?;
+        state
+            .open
+            .remove(&authorization.id)
+            .map(|_| ())
+            .ok_or_else(|| PaymentError::Unknown(authorization.id.clone()))
+    }
+
+    fn refund(
+        &self,
+        payment: &Payment,
+        amount: Money<StoreCurrency>,
+    ) -> Result<Refund, PaymentError> {
+        let mut state = self.state.lock().unwrap();
+        state.call(Operation::Refund)?;
+        if amount <= Money::zero() {
+            return Err(PaymentError::InvalidAmount(amount));
+        }
+        let (paid, refunded) = state
+            .payments
+            .get(&payment.reference)
+            .map(|(paid, refunded)| (paid.amount, *refunded))
+            .ok_or_else(|| PaymentError::Unknown(payment.reference.clone()))?;
+        let refundable = paid.checked_sub(refunded)?;
+        if amount > refundable {
+            return Err(PaymentError::RefundTooLarge {
+                reference: payment.reference.clone(),
+                requested: amount,
+                refundable,
+            });
+        }
+        let refund = Refund {
+            reference: state.id("refund"),
+            payment: payment.reference.clone(),
+            amount,
+        };
+        if let Some((_, refunded)) = state.payments.get_mut(&payment.reference) {
+            *refunded = refunded.checked_add(amount)?;
+        }
+        Ok(refund)
+    }
+}
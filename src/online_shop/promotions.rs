@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use super::{
+    cart, Clock, CustomerId, LineItem, Money, MoneyError, Rounding, Sku, StoreCurrency,
+    SystemClock, Timestamp,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Discount {
+    // A whole percentage off the subtotal, e.g. 10 for 10%.
+    PercentOff(u32),
+    // An amount off the subtotal, never taking it below zero.
+    FixedOff(Money<StoreCurrency>),
+    // For every `buy` of the product, the next `get` of it are free.
+    BuyXGetY { sku: Sku, buy: u32, get: u32 },
+}
+
+// A code customers can enter at checkout, along with the conditions for it to
+// be accepted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coupon {
+    pub code: String,
+    pub discount: Discount,
+    pub minimum_spend: Option<Money<StoreCurrency>>,
+    // How many orders each customer can use it on.
+    pub uses_per_customer: Option<u32>,
+    pub expires_at: Option<Timestamp>,
+}
+
+impl Coupon {
+    pub fn new(code: impl Into<String>, discount: Discount) -> Self {
+        Coupon {
+            code: code.into(),
+            discount,
+            minimum_spend: None,
+            uses_per_customer: None,
+            expires_at: None,
+        }
+    }
+
+    pub fn with_minimum_spend(mut self, minimum_spend: Money<StoreCurrency>) -> Self {
+        self.minimum_spend = Some(minimum_spend);
+        self
+    }
+
+    pub fn with_uses_per_customer(mut self, uses: u32) -> Self {
+        self.uses_per_customer = Some(uses);
+        self
+    }
+
+    pub fn with_expiry(mut self, expires_at: Timestamp) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CouponError {
+    UnknownCode(String),
+    Expired {
+        code: String,
+        expired_at: Timestamp,
+    },
+    MinimumSpendNotMet {
+        code: String,
+        minimum_spend: Money<StoreCurrency>,
+        subtotal: Money<StoreCurrency>,
+    },
+    UsageLimitReached {
+        code: String,
+        uses_per_customer: u32,
+    },
+    // The coupon is valid, but there's nothing in the cart it takes anything
+    // off, e.g. buy-X-get-Y without enough of the product.
+    NothingToDiscount(String),
+    Money(MoneyError),
+}
+
+impl fmt::Display for CouponError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CouponError::UnknownCode(code) => write!(f, "there's no coupon `{}`", code),
+            CouponError::Expired { code, expired_at } => {
+                write!(f, "coupon `{}` expired at {}", code, expired_at)
+            }
+            CouponError::MinimumSpendNotMet {
+                code,
+                minimum_spend,
+                subtotal,
+            } => write!(
+                f,
+                "coupon `{}` needs a spend of at least {}, but the cart is {}",
+                code, minimum_spend, subtotal
+            ),
+            CouponError::UsageLimitReached {
+                code,
+                uses_per_customer,
+            } => write!(
+                f,
+                "coupon `{}` can only be used {} time(s) per customer",
+                code, uses_per_customer
+            ),
+            CouponError::NothingToDiscount(code) => {
+                write!(f, "coupon `{}` doesn't apply to anything in the cart", code)
+            }
+            CouponError::Money(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for CouponError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CouponError::Money(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<MoneyError> for CouponError {
+    fn from(err: MoneyError) -> Self {
+        CouponError::Money(err)
+    }
+}
+
+// The coupons on offer and how often each customer has used them. This is
+// shared by every customer of a shop, so all clones refer to the same coupons
+// and usage.
+#[derive(Clone)]
+pub struct PromotionEngine {
+    state: Arc<Mutex<Promotions>>,
+    clock: Arc<dyn Clock>,
+}
+
+#[derive(Default)]
+struct Promotions {
+    coupons: HashMap<String, Coupon>,
+    uses: HashMap<(CustomerId, String), u32>,
+}
+
+impl PromotionEngine {
+    pub fn new(clock: impl Clock + 'static) -> Self {
+        PromotionEngine {
+            state: Arc::default(),
+            clock: Arc::new(clock),
+        }
+    }
+
+    pub fn with_coupon(self, coupon: Coupon) -> Self {
+        self.add_coupon(coupon);
+        self
+    }
+
+    // Replaces any coupon with the same code.
+    pub fn add_coupon(&self, coupon: Coupon) {
+        let mut state = self.state.lock().unwrap();
+        state.coupons.insert(coupon.code.clone(), coupon);
+    }
+
+    // How many orders the customer has paid for with the coupon.
+    pub fn uses(&self, customer: &CustomerId, code: &str) -> u32 {
+        let state = self.state.lock().unwrap();
+        let key = (customer.clone(), code.to_string());
+        state.uses.get(&key).copied().unwrap_or_default()
+    }
+
+    // Checks whether the customer can use the coupon on the cart right now,
+    // and if so, how much it takes off the cart's subtotal.
+    pub fn discount(
+        &self,
+        customer: &CustomerId,
+        code: &str,
+        cart: &[LineItem],
+    ) -> Result<Money<StoreCurrency>, CouponError> {
+        let coupon = self
+            .state
+            .lock()
+            .unwrap()
+            .coupons
+            .get(code)
+            .cloned()
+            .ok_or_else(|| CouponError::UnknownCode(code.to_string()))?;
+
+        if let Some(expired_at) = coupon.expires_at {
+            if self.clock.now() >= expired_at {
+                return Err(CouponError::Expired {
+                    code: coupon.code,
+                    expired_at,
+                });
+            }
+        }
+        if let Some(uses_per_customer) = coupon.uses_per_customer {
+            if self.uses(customer, code) >= uses_per_customer {
+                return Err(CouponError::UsageLimitReached {
+                    code: coupon.code,
+                    uses_per_customer,
+                });
+            }
+        }
+        let subtotal = cart::subtotal(cart)?;
+        if let Some(minimum_spend) = coupon.minimum_spend {
+            if subtotal < minimum_spend {
+                return Err(CouponError::MinimumSpendNotMet {
+                    code: coupon.code,
+                    minimum_spend,
+                    subtotal,
+                });
+            }
+        }
+
+        let discount = match &coupon.discount {
+            Discount::PercentOff(percent) => {
+                subtotal.checked_mul_ratio(i64::from(*percent), 100, Rounding::Down)?
+            }
+            Discount::FixedOff(amount) => *amount,
+            Discount::BuyXGetY { sku, buy, get } => {
+                // A deal that adds up to more than a cart can hold of anything
+                // (or to nothing at all) never applies.
+                let deal = buy.checked_add(*get).filter(|deal| *deal > 0);
+                match (cart.iter().find(|line| line.product.sku == *sku), deal) {
+                    (Some(line), Some(deal)) => {
+                        let free = line.quantity.get() / deal * get;
+                        line.product.unit_price.checked_mul(i64::from(free))?
+                    }
+                    _ => Money::zero(),
+                }
+            }
+        };
+        if discount <= Money::zero() {
+            return Err(CouponError::NothingToDiscount(coupon.code));
+        }
+        // Nothing is ever given away for less than nothing.
+        Ok(discount.min(subtotal))
+    }
+
+    // Counts an order paid for with the coupon towards the customer's limit.
+    pub(super) fn redeem(&self, customer: &CustomerId, code: &str) {
+        let mut state = self.state.lock().unwrap();
+        *state
+            .uses
+            .entry((customer.clone(), code.to_string()))
+            .or_default() += 1;
+    }
+}
+
+// No coupons, on the system clock.
+impl Default for PromotionEngine {
+    fn default() -> Self {
+        PromotionEngine::new(SystemClock)
+    }
+}
@@ -0,0 +1,338 @@
+use std::error::Error;
+use std::fmt;
+use std::num::NonZeroU32;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    Clock, Delivered, Money, MoneyError, Order, PaymentError, PaymentGateway, Refunded,
+    ReturnReceived, ReturnRejected, ReturnRequested, Rounding, Sku, StoreCurrency, SystemClock,
+    Timestamp,
+};
+
+// How many of a product of the order are being sent back.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReturnLine {
+    pub sku: Sku,
+    pub quantity: NonZeroU32,
+}
+
+impl ReturnLine {
+    pub fn new(sku: impl Into<Sku>, quantity: NonZeroU32) -> Self {
+        ReturnLine {
+            sku: sku.into(),
+            quantity,
+        }
+    }
+}
+
+// What the customer asked to send back, and why. There's at most one line per
+// product, in the order they were asked for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReturnRequest {
+    pub lines: Vec<ReturnLine>,
+    pub reason: String,
+    pub requested_at: Timestamp,
+}
+
+// How much is given back for a line of the return.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RefundLine {
+    pub sku: Sku,
+    pub amount: Money<StoreCurrency>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReturnError {
+    // The return window closed at `deadline`.
+    WindowClosed {
+        delivered_at: Timestamp,
+        deadline: Timestamp,
+    },
+    NothingToReturn,
+    NotInOrder(Sku),
+    TooMany {
+        sku: Sku,
+        requested: u32,
+        ordered: u32,
+    },
+    // A refund for a line that isn't being returned.
+    NotReturned(Sku),
+    // More than was paid for what's being returned.
+    RefundTooLarge {
+        sku: Sku,
+        requested: Money<StoreCurrency>,
+        refundable: Money<StoreCurrency>,
+    },
+    Payment(PaymentError),
+    Money(MoneyError),
+}
+
+impl fmt::Display for ReturnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReturnError::WindowClosed { deadline, .. } => {
+                write!(f, "the order could only be returned until {}", deadline)
+            }
+            ReturnError::NothingToReturn => write!(f, "nothing was asked to be returned"),
+            ReturnError::NotInOrder(sku) => write!(f, "`{}` isn't part of the order", sku),
+            ReturnError::TooMany {
+                sku,
+                requested,
+                ordered,
+            } => write!(
+                f,
+                "can't return {} x {}, only {} were ordered",
+                requested, sku, ordered
+            ),
+            ReturnError::NotReturned(sku) => write!(f, "`{}` isn't being returned", sku),
+            ReturnError::RefundTooLarge {
+                sku,
+                requested,
+                refundable,
+            } => write!(
+                f,
+                "can't refund {} for {}, only {} was paid for it",
+                requested, sku, refundable
+            ),
+            ReturnError::Payment(err) => write!(f, "{}", err),
+            ReturnError::Money(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for ReturnError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReturnError::Payment(err) => Some(err),
+            ReturnError::Money(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<PaymentError> for ReturnError {
+    fn from(err: PaymentError) -> Self {
+        ReturnError::Payment(err)
+    }
+}
+
+impl From<MoneyError> for ReturnError {
+    fn from(err: MoneyError) -> Self {
+        ReturnError::Money(err)
+    }
+}
+
+// How long after delivery an order can still be returned, by the clock.
+#[derive(Clone)]
+pub struct ReturnPolicy {
+    window: Duration,
+    clock: Arc<dyn Clock>,
+}
+
+impl ReturnPolicy {
+    // Returns are accepted for 30 days unless said otherwise.
+    pub const DEFAULT_WINDOW: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+    pub fn new(clock: impl Clock + 'static) -> Self {
+        ReturnPolicy {
+            window: Self::DEFAULT_WINDOW,
+            clock: Arc::new(clock),
+        }
+    }
+
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    // The last moment an order delivered at `delivered_at` can be returned.
+    pub fn deadline(&self, delivered_at: Timestamp) -> Timestamp {
+        delivered_at + self.window
+    }
+}
+
+// 30 days, on the system clock.
+impl Default for ReturnPolicy {
+    fn default() -> Self {
+        ReturnPolicy::new(SystemClock)
+    }
+}
+
+impl Order<Delivered> {
+    // "Delivered" -> "ReturnRequested", handing the order back along with the
+    // reason if it can't be returned (e.g. the window has closed, or more is
+    // being returned than was ordered). Lines of the same product are added up.
+    pub fn request_return(
+        self,
+        policy: &ReturnPolicy,
+        lines: Vec<ReturnLine>,
+        reason: impl Into<String>,
+    ) -> Result<Order<ReturnRequested>, (Self, ReturnError)> {
+        let now = policy.clock.now();
+        let deadline = policy.deadline(self.state.delivered_at);
+        if now > deadline {
+            let err = ReturnError::WindowClosed {
+                delivered_at: self.state.delivered_at,
+                deadline,
+            };
+            return Err((self, err));
+        }
+        let lines = match self.check_return(lines) {
+            Ok(lines) => lines,
+            Err(err) => return Err((self, err)),
+        };
+        let request = ReturnRequest {
+            lines,
+            reason: reason.into(),
+            requested_at: now,
+        };
+        Ok(self.transition(|_| ReturnRequested { request }))
+    }
+
+    // Merges lines of the same product, and checks each against the order.
+    fn check_return(&self, lines: Vec<ReturnLine>) -> Result<Vec<ReturnLine>, ReturnError> {
+        let mut merged: Vec<ReturnLine> = vec![];
+        for line in lines {
+            match merged.iter_mut().find(|merged| merged.sku == line.sku) {
+                Some(merged) => {
+                    merged.quantity = merged.quantity.saturating_add(line.quantity.get())
+                }
+                None => merged.push(line),
+            }
+        }
+        if merged.is_empty() {
+            return Err(ReturnError::NothingToReturn);
+        }
+        for line in &merged {
+            let ordered = self
+                .lines
+                .iter()
+                .find(|ordered| ordered.product.sku == line.sku)
+                .ok_or_else(|| ReturnError::NotInOrder(line.sku.clone()))?;
+            if line.quantity > ordered.quantity {
+                return Err(ReturnError::TooMany {
+                    sku: line.sku.clone(),
+                    requested: line.quantity.get(),
+                    ordered: ordered.quantity.get(),
+                });
+            }
+        }
+        Ok(merged)
+    }
+}
+
+impl Order<ReturnRequested> {
+    // "ReturnRequested" -> "ReturnReceived", once the parcel is back.
+    pub fn receive_return(self) -> Order<ReturnReceived> {
+        self.transition(|ReturnRequested { request }| ReturnReceived { request })
+    }
+}
+
+impl Order<ReturnReceived> {
+    // The most that can be refunded for each line being returned: its share
+    // of what was actually paid, which has any discount taken off and any tax
+    // charged on top included. Amounts are rounded down, so they never add up
+    // to more than was paid.
+    pub fn refund_due(&self) -> Result<Vec<RefundLine>, MoneyError> {
+        // Every line was checked against the order when the return was
+        // requested, so each is found.
+        self.state
+            .request
+            .lines
+            .iter()
+            .filter_map(|returned| {
+                let (index, ordered) = self
+                    .lines
+                    .iter()
+                    .enumerate()
+                    .find(|(_, line)| line.product.sku == returned.sku)?;
+                Some((index, ordered, returned))
+            })
+            .map(|(index, ordered, returned)| {
+                // What the line cost, which is what the tax was worked out on.
+                let paid = self.tax.lines[index].gross;
+                let amount = paid.checked_mul_ratio(
+                    i64::from(returned.quantity.get()),
+                    i64::from(ordered.quantity.get()),
+                    Rounding::Down,
+                )?;
+                Ok(RefundLine {
+                    sku: returned.sku.clone(),
+                    amount,
+                })
+            })
+            .collect()
+    }
+
+    // "ReturnReceived" -> "Refunded", giving back the amounts of `lines`
+    // through the gateway in a single refund. Pass `refund_due()` to refund
+    // everything that was paid for what's returned, or less for lines that
+    // came back damaged. The order is handed back along with the reason if a
+    // line isn't being returned, asks for more than was paid for it, or the
+    // gateway doesn't go through with the refund.
+    pub fn refund(
+        self,
+        gateway: &dyn PaymentGateway,
+        lines: Vec<RefundLine>,
+    ) -> Result<Order<Refunded>, (Self, ReturnError)> {
+        let amount = match self.check_refund(&lines) {
+            Ok(amount) => amount,
+            Err(err) => return Err((self, err)),
+        };
+        let refund = if amount == Money::zero() {
+            None
+        } else {
+            match gateway.refund(&self.payment, amount) {
+                Ok(refund) => Some(refund),
+                Err(err) => return Err((self, err.into())),
+            }
+        };
+        Ok(self.transition(|ReturnReceived { request }| Refunded {
+            request,
+            lines,
+            refund,
+        }))
+    }
+
+    // "ReturnReceived" -> "ReturnRejected", e.g. when what came back isn't
+    // what was sold. Nothing is refunded.
+    pub fn reject_return(self, reason: impl Into<String>) -> Order<ReturnRejected> {
+        self.transition(|ReturnReceived { request }| ReturnRejected {
+            request,
+            reason: reason.into(),
+        })
+    }
+
+    // The sum of the refund, once every line is checked against what's due.
+    // Several lines for the same product count towards the same amount due.
+    fn check_refund(&self, lines: &[RefundLine]) -> Result<Money<StoreCurrency>, ReturnError> {
+        let due = self.refund_due()?;
+        for line in lines {
+            if line.amount.is_negative() {
+                return Err(PaymentError::InvalidAmount(line.amount).into());
+            }
+            let refundable = due
+                .iter()
+                .find(|due| due.sku == line.sku)
+                .ok_or_else(|| ReturnError::NotReturned(line.sku.clone()))?
+                .amount;
+            let requested = Money::sum(
+                lines
+                    .iter()
+                    .filter(|other| other.sku == line.sku)
+                    .map(|other| other.amount),
+            )?;
+            if requested > refundable {
+                return Err(ReturnError::RefundTooLarge {
+                    sku: line.sku.clone(),
+                    requested,
+                    refundable,
+                });
+            }
+        }
+        Ok(Money::sum(lines.iter().map(|line| line.amount))?)
+    }
+}
@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    cart, CustomerId, LineItem, Money, MoneyError, Region, ReservationId, StoreCurrency, Timestamp,
+};
+
+// What's known about a customer who left without paying for what they had put
+// in their cart, e.g. for following up on abandoned carts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AbandonedSession {
+    pub customer: CustomerId,
+    pub started_at: Timestamp,
+    pub left_at: Timestamp,
+    // The cart as it was just before it was last emptied.
+    pub last_cart: Vec<LineItem>,
+    pub reached_checkout: bool,
+    pub coupon: Option<String>,
+    pub region: Option<Region>,
+}
+
+impl AbandonedSession {
+    // What the last cart would have cost, before any discount or tax.
+    pub fn value(&self) -> Result<Money<StoreCurrency>, MoneyError> {
+        cart::subtotal(&self.last_cart)
+    }
+}
+
+// Keeps track of the customer's visit as they go through the flow, so there's
+// something to summarise if they leave. The cart is emptied before leaving, so
+// the last cart that wasn't empty is kept around. Stock is reserved under the
+// visit's own id while checking out.
+pub(super) struct Session {
+    pub(super) reservation: ReservationId,
+    pub(super) started_at: Timestamp,
+    pub(super) last_cart: Vec<LineItem>,
+    pub(super) reached_checkout: bool,
+}
+
+impl Session {
+    pub(super) fn new(started_at: Timestamp) -> Self {
+        Session {
+            reservation: ReservationId::unique(),
+            started_at,
+            last_cart: vec![],
+            reached_checkout: false,
+        }
+    }
+
+    pub(super) fn update(&mut self, cart: &[LineItem], at_checkout: bool) {
+        if !cart.is_empty() {
+            self.last_cart = cart.to_vec();
+        }
+        self.reached_checkout |= at_checkout;
+    }
+}
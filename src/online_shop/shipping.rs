@@ -0,0 +1,51 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+// How the order is sent on its way once it's paid for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ShippingOption {
+    Standard,
+    Express,
+}
+
+// As typed in by users of the `stated` binary, e.g. "express".
+impl fmt::Display for ShippingOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let option = match self {
+            ShippingOption::Standard => "standard",
+            ShippingOption::Express => "express",
+        };
+        f.pad(option)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseShippingOptionError(pub String);
+
+impl fmt::Display for ParseShippingOptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown shipping option `{}`, try standard or express",
+            self.0
+        )
+    }
+}
+
+impl Error for ParseShippingOptionError {}
+
+// Reads back what `Display` writes.
+impl FromStr for ShippingOption {
+    type Err = ParseShippingOptionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "standard" => Ok(ShippingOption::Standard),
+            "express" => Ok(ShippingOption::Express),
+            other => Err(ParseShippingOptionError(other.to_string())),
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    Clock, Coupon, Discount, InMemoryInventory, Inventory, MockGateway, Money, OrderId,
+    PaymentGateway, PromotionEngine, SystemClock, TaxPolicy, TaxTable, Timestamp,
+};
+
+// Who a customer is, for anything that has to be tracked across their visits
+// (e.g. how often they've used a coupon).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CustomerId(String);
+
+impl CustomerId {
+    pub fn new(id: impl Into<String>) -> Self {
+        CustomerId(id.into())
+    }
+
+    // A fresh id for a customer who hasn't said who they are, which is never
+    // the same as any other guest's.
+    pub fn guest() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        CustomerId(format!("guest-{}", NEXT.fetch_add(1, Ordering::Relaxed)))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for CustomerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&self.0)
+    }
+}
+
+// The services every customer of a shop relies on, and which outlive any one
+// of them. Cloning a shop is cheap, and clones share the same state.
+#[derive(Clone)]
+pub struct Shop {
+    pub promotions: PromotionEngine,
+    pub taxes: Arc<dyn TaxPolicy>,
+    pub inventory: Arc<dyn Inventory>,
+    // The gateway payments go through when the customer is driven through
+    // `AnyCustomer::apply()`. `finalise_payment()` on a typed customer is
+    // handed its gateway instead.
+    pub payments: Arc<dyn PaymentGateway>,
+    // What orders and sessions are timestamped with.
+    pub clock: Arc<dyn Clock>,
+    // The number of the last order placed, which orders are numbered after.
+    orders: Arc<AtomicU64>,
+}
+
+// No coupons, no tax rules (so there's nowhere to ship to), no stock tracked
+// (so there's always enough of everything), and a mock gateway that accepts
+// every payment, on the system clock.
+impl Default for Shop {
+    fn default() -> Self {
+        Shop {
+            promotions: PromotionEngine::default(),
+            taxes: Arc::new(TaxTable::new()),
+            inventory: Arc::new(InMemoryInventory::default()),
+            payments: Arc::new(MockGateway::new()),
+            clock: Arc::new(SystemClock),
+            orders: Arc::default(),
+        }
+    }
+}
+
+impl Shop {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_promotions(mut self, promotions: PromotionEngine) -> Self {
+        self.promotions = promotions;
+        self
+    }
+
+    pub fn with_taxes(mut self, taxes: impl TaxPolicy + 'static) -> Self {
+        self.taxes = Arc::new(taxes);
+        self
+    }
+
+    pub fn with_inventory(mut self, inventory: impl Inventory + 'static) -> Self {
+        self.inventory = Arc::new(inventory);
+        self
+    }
+
+    pub fn with_payments(mut self, payments: impl PaymentGateway + 'static) -> Self {
+        self.payments = Arc::new(payments);
+        self
+    }
+
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    // A fresh id for an order of this shop ("order-1", "order-2" ...), which
+    // is never the same as any other order's.
+    pub(super) fn next_order_id(&self) -> OrderId {
+        let number = self.orders.fetch_add(1, Ordering::Relaxed) + 1;
+        OrderId::new(format!("order-{}", number))
+    }
+
+    // The shop used by the `stated` binary and the checked-in scenarios,
+    // selling from `Catalogue::demo()` (with a few of the products running low)
+    // and taxing by `TaxTable::demo()`.
+    pub fn demo() -> Self {
+        let eur = Money::from_minor;
+        let promotions = PromotionEngine::new(SystemClock)
+            .with_coupon(
+                Coupon::new("WELCOME10", Discount::PercentOff(10)).with_uses_per_customer(1),
+            )
+            .with_coupon(
+                Coupon::new("FIVEOFF", Discount::FixedOff(eur(500))).with_minimum_spend(eur(3000)),
+            )
+            .with_coupon(Coupon::new(
+                "TEATIME",
+                Discount::BuyXGetY {
+                    sku: "tea".into(),
+                    buy: 2,
+                    get: 1,
+                },
+            ))
+            // 2020-01-01T00:00:00Z
+            .with_coupon(
+                Coupon::new("NEWYEAR20", Discount::PercentOff(20))
+                    .with_expiry(Timestamp::from_unix(1_577_836_800)),
+            );
+        let inventory = InMemoryInventory::new(SystemClock)
+            .with_stock("tea", 100)
+            .with_stock("mug", 20)
+            .with_stock("socks", 20)
+            .with_stock("book", 5)
+            .with_stock("lamp", 3)
+            .with_stock("plant", 2);
+        Shop::new()
+            .with_promotions(promotions)
+            .with_taxes(TaxTable::demo())
+            .with_inventory(inventory)
+    }
+}
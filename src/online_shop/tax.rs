@@ -0,0 +1,445 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use super::{LineItem, Money, MoneyError, Rounding, Sku, StoreCurrency};
+
+// What kind of product something is as far as tax is concerned, e.g. food is
+// often taxed at a lower rate. Products are "standard" unless said otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TaxCategory(String);
+
+impl TaxCategory {
+    pub fn new(category: impl Into<String>) -> Self {
+        TaxCategory(category.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for TaxCategory {
+    fn default() -> Self {
+        TaxCategory::new("standard")
+    }
+}
+
+impl From<&str> for TaxCategory {
+    fn from(category: &str) -> Self {
+        TaxCategory::new(category)
+    }
+}
+
+impl fmt::Display for TaxCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&self.0)
+    }
+}
+
+// Where an order is shipped to (and billed in), which decides how it's taxed,
+// e.g. "NL" or "DE".
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Region(String);
+
+impl Region {
+    pub fn new(region: impl Into<String>) -> Self {
+        Region(region.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Region {
+    fn from(region: &str) -> Self {
+        Region::new(region)
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&self.0)
+    }
+}
+
+// A percentage with up to two decimals, stored in basis points (hundredths of
+// a percent) so there's no floating point involved. Written as e.g. "21%" or
+// "5.5%".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TaxRate(u32);
+
+impl TaxRate {
+    pub fn from_basis_points(basis_points: u32) -> Self {
+        TaxRate(basis_points)
+    }
+
+    pub fn basis_points(&self) -> u32 {
+        self.0
+    }
+}
+
+impl fmt::Display for TaxRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (whole, fraction) = (self.0 / 100, self.0 % 100);
+        let rate = match fraction {
+            0 => format!("{}%", whole),
+            _ if fraction % 10 == 0 => format!("{}.{}%", whole, fraction / 10),
+            _ => format!("{}.{:02}%", whole, fraction),
+        };
+        f.pad(&rate)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseTaxRateError(pub String);
+
+impl fmt::Display for ParseTaxRateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` isn't a percentage like 21% or 5.5%", self.0)
+    }
+}
+
+impl Error for ParseTaxRateError {}
+
+// Reads back what `Display` writes.
+impl FromStr for TaxRate {
+    type Err = ParseTaxRateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseTaxRateError(s.trim().to_string());
+        let rate = s.trim().strip_suffix('%').ok_or_else(invalid)?;
+        let (whole, fraction) = rate.split_once('.').unwrap_or((rate, ""));
+        let digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+        if whole.is_empty() || !digits(whole) || !digits(fraction) || fraction.len() > 2 {
+            return Err(invalid());
+        }
+        let whole: u32 = whole.parse().map_err(|_| invalid())?;
+        let fraction: u32 = format!("{:0<2}", fraction).parse().map_err(|_| invalid())?;
+        whole
+            .checked_mul(100)
+            .and_then(|rate| rate.checked_add(fraction))
+            .map(TaxRate)
+            .ok_or_else(invalid)
+    }
+}
+
+impl TryFrom<String> for TaxRate {
+    type Error = ParseTaxRateError;
+
+    fn try_from(rate: String) -> Result<Self, Self::Error> {
+        rate.parse()
+    }
+}
+
+impl From<TaxRate> for String {
+    fn from(rate: TaxRate) -> Self {
+        rate.to_string()
+    }
+}
+
+// Whether the prices in the catalogue already include the tax (as is usual for
+// consumers in the EU), or it's added on top of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pricing {
+    Inclusive,
+    Exclusive,
+}
+
+// How products of a category are taxed when shipped to a region.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaxRule {
+    pub region: Region,
+    pub category: TaxCategory,
+    pub rate: TaxRate,
+    pub pricing: Pricing,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaxError {
+    // The breakdown depends on where the order goes, which isn't known yet.
+    NoRegion,
+    NoRule {
+        region: Region,
+        category: TaxCategory,
+    },
+    Money(MoneyError),
+}
+
+impl fmt::Display for TaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaxError::NoRegion => write!(f, "the region to ship to isn't known yet"),
+            TaxError::NoRule { region, category } => {
+                write!(f, "no tax rule for {} products in {}", category, region)
+            }
+            TaxError::Money(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for TaxError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TaxError::Money(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<MoneyError> for TaxError {
+    fn from(err: MoneyError) -> Self {
+        TaxError::Money(err)
+    }
+}
+
+// How tax is charged on a single line of the cart. `gross` is what the
+// customer pays for it, which is `net` plus `tax` whichever way it's priced,
+// after `discount` (the line's share of the coupon's discount) has been taken
+// off the price.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaxLine {
+    pub sku: Sku,
+    pub category: TaxCategory,
+    pub rate: TaxRate,
+    pub pricing: Pricing,
+    pub discount: Money<StoreCurrency>,
+    pub net: Money<StoreCurrency>,
+    pub tax: Money<StoreCurrency>,
+    pub gross: Money<StoreCurrency>,
+}
+
+// The tax on a cart, line by line, along with the sums of every line.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaxBreakdown {
+    pub region: Region,
+    pub lines: Vec<TaxLine>,
+    pub net: Money<StoreCurrency>,
+    pub tax: Money<StoreCurrency>,
+    pub gross: Money<StoreCurrency>,
+}
+
+// An itemised table, one line per line of the cart followed by the sums.
+impl fmt::Display for TaxBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Tax for shipping to {}:", self.region)?;
+        for line in &self.lines {
+            let pricing = match line.pricing {
+                Pricing::Inclusive => "incl.",
+                Pricing::Exclusive => "excl.",
+            };
+            writeln!(
+                f,
+                "  {:<8}{:<10}{:>6} {}{:>14} net{:>14} tax{:>14}",
+                line.sku, line.category, line.rate, pricing, line.net, line.tax, line.gross
+            )?;
+        }
+        write!(
+            f,
+            "  {:<31}{:>14} net{:>14} tax{:>14}",
+            "total", self.net, self.tax, self.gross
+        )
+    }
+}
+
+// Decides how products are taxed. The shop only ever asks for the rule of a
+// region and category, so where the rules come from is up to the policy.
+pub trait TaxPolicy: Send + Sync {
+    fn rule(&self, region: &Region, category: &TaxCategory) -> Option<TaxRule>;
+
+    // The tax on every line of the cart when shipped to `region`, once the
+    // coupon's `discount` has been taken off, so tax is only charged on what's
+    // actually paid. Tax is rounded per line, to the nearest cent.
+    fn breakdown(
+        &self,
+        region: &Region,
+        cart: &[LineItem],
+        discount: Money<StoreCurrency>,
+    ) -> Result<TaxBreakdown, TaxError> {
+        let discounts = spread(cart, discount)?;
+        let lines = cart
+            .iter()
+            .zip(discounts)
+            .map(|(line, discount)| {
+                let category = &line.product.tax_category;
+                let rule = self
+                    .rule(region, category)
+                    .ok_or_else(|| TaxError::NoRule {
+                        region: region.clone(),
+                        category: category.clone(),
+                    })?;
+                let price = line.total()?.checked_sub(discount)?;
+                let rate = i64::from(rule.rate.basis_points());
+                let (net, tax, gross) = match rule.pricing {
+                    Pricing::Inclusive => {
+                        let tax = price.checked_mul_ratio(rate, 10_000 + rate, Rounding::HalfUp)?;
+                        (price.checked_sub(tax)?, tax, price)
+                    }
+                    Pricing::Exclusive => {
+                        let tax = price.checked_mul_ratio(rate, 10_000, Rounding::HalfUp)?;
+                        (price, tax, price.checked_add(tax)?)
+                    }
+                };
+                Ok(TaxLine {
+                    sku: line.product.sku.clone(),
+                    category: category.clone(),
+                    rate: rule.rate,
+                    pricing: rule.pricing,
+                    discount,
+                    net,
+                    tax,
+                    gross,
+                })
+            })
+            .collect::<Result<Vec<_>, TaxError>>()?;
+
+        Ok(TaxBreakdown {
+            region: region.clone(),
+            net: Money::sum(lines.iter().map(|line| line.net))?,
+            tax: Money::sum(lines.iter().map(|line| line.tax))?,
+            gross: Money::sum(lines.iter().map(|line| line.gross))?,
+            lines,
+        })
+    }
+}
+
+// Each line's share of `discount`, in proportion to what the line costs. Shares
+// are rounded down, and the cents that leaves over go to the lines that were
+// rounded down the most, so the shares add up to the discount exactly and none
+// is more than its line costs.
+fn spread(
+    cart: &[LineItem],
+    discount: Money<StoreCurrency>,
+) -> Result<Vec<Money<StoreCurrency>>, MoneyError> {
+    let totals = cart
+        .iter()
+        .map(LineItem::total)
+        .collect::<Result<Vec<_>, _>>()?;
+    let subtotal = Money::sum(totals.iter().copied())?;
+    if subtotal == Money::zero() {
+        return Ok(vec![Money::zero(); totals.len()]);
+    }
+
+    let mut shares = totals
+        .iter()
+        .map(|total| total.checked_mul_ratio(discount.minor(), subtotal.minor(), Rounding::Down))
+        .collect::<Result<Vec<_>, _>>()?;
+    let left_over = discount.checked_sub(Money::sum(shares.iter().copied())?)?;
+    let remainder = |total: &Money<StoreCurrency>| {
+        i128::from(total.minor()) * i128::from(discount.minor()) % i128::from(subtotal.minor())
+    };
+    let mut by_remainder: Vec<usize> = (0..totals.len()).collect();
+    by_remainder.sort_by_key(|&index| Reverse(remainder(&totals[index])));
+    for &index in by_remainder.iter().take(left_over.minor() as usize) {
+        shares[index] = shares[index].checked_add(Money::from_minor(1))?;
+    }
+    Ok(shares)
+}
+
+#[derive(Debug)]
+pub enum TaxConfigError {
+    Io(io::Error),
+    Parse(serde_json::Error),
+    // Two rules for the same region and category, so it's unclear which one
+    // was meant.
+    DuplicateRule {
+        region: Region,
+        category: TaxCategory,
+    },
+}
+
+impl fmt::Display for TaxConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaxConfigError::Io(err) => write!(f, "can't read the tax rules: {}", err),
+            TaxConfigError::Parse(err) => write!(f, "invalid tax rules: {}", err),
+            TaxConfigError::DuplicateRule { region, category } => write!(
+                f,
+                "more than one tax rule for {} products in {}",
+                category, region
+            ),
+        }
+    }
+}
+
+impl Error for TaxConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TaxConfigError::Io(err) => Some(err),
+            TaxConfigError::Parse(err) => Some(err),
+            TaxConfigError::DuplicateRule { .. } => None,
+        }
+    }
+}
+
+// A `TaxPolicy` that's just a table of rules, one per region and category.
+// Anything not in the table can't be shipped there.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TaxTable {
+    rules: BTreeMap<(Region, TaxCategory), TaxRule>,
+}
+
+impl TaxTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Replaces any rule for the same region and category.
+    pub fn with_rule(mut self, rule: TaxRule) -> Self {
+        let key = (rule.region.clone(), rule.category.clone());
+        self.rules.insert(key, rule);
+        self
+    }
+
+    // Reads the rules from a JSON file holding a list of `TaxRule`s, e.g.
+    // `{ "region": "NL", "category": "food", "rate": "9%", "pricing": "inclusive" }`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TaxConfigError> {
+        let source = fs::read_to_string(path).map_err(TaxConfigError::Io)?;
+        Self::from_json(&source)
+    }
+
+    pub fn from_json(source: &str) -> Result<Self, TaxConfigError> {
+        let rules: Vec<TaxRule> = serde_json::from_str(source).map_err(TaxConfigError::Parse)?;
+        let mut table = TaxTable::new();
+        for rule in rules {
+            if table.rule(&rule.region, &rule.category).is_some() {
+                return Err(TaxConfigError::DuplicateRule {
+                    region: rule.region,
+                    category: rule.category,
+                });
+            }
+            table = table.with_rule(rule);
+        }
+        Ok(table)
+    }
+
+    // The rules in `config/tax_rules.json`, which are built into the binary so
+    // the demo shop works from anywhere.
+    pub fn demo() -> Self {
+        Self::from_json(include_str!("../../config/tax_rules.json"))
+            .expect("the demo tax rules are valid")
+    }
+
+    // By region, then category.
+    pub fn rules(&self) -> impl Iterator<Item = &TaxRule> {
+        self.rules.values()
+    }
+}
+
+impl TaxPolicy for TaxTable {
+    fn rule(&self, region: &Region, category: &TaxCategory) -> Option<TaxRule> {
+        self.rules.get(&(region.clone(), category.clone())).cloned()
+    }
+}
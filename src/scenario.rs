@@ -0,0 +1,350 @@
+// Scripted shopping sessions, so customer journeys can be checked in as plain
+// files instead of being written out in code. A scenario is one step per line:
+//
+//     # Anything after a '#' is a comment.
+//     add mug
+//     add mug
+//     add tea
+//     expect state Shopping
+//     expect cart mug:2 tea
+//     checkout
+//     coupon WELCOME10
+//     expect total 25.65 EUR
+//     ship NL
+//     shipping standard
+//     payment card
+//     pay
+//     expect state Paid
+//
+// Actions use the same commands as the `stated` REPL, and the session always
+// starts by visiting the site. The cart is given as one `<sku>:<quantity>` per
+// line item, in the order they were added, where a quantity of 1 can be left
+// out. The total is the cart's after any coupon, but before any tax charged on
+// top of the prices (e.g. in US-NY), so it's only what's paid where prices
+// include tax.
+
+use std::error::Error;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::online_shop::{
+    Action, AnyCustomer, ApplyError, Catalogue, Customer, CustomerId, LineItem, Money, NoopSink,
+    ParseMoneyError, Shop, Sku, StoreCurrency,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    Do(Action),
+    ExpectState(String),
+    ExpectCart(Vec<CartLine>),
+    ExpectTotal(Money<StoreCurrency>),
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Do(action) => write!(f, "{}", action),
+            Step::ExpectState(state) => write!(f, "expect state {}", state),
+            Step::ExpectCart(cart) => {
+                write!(f, "expect cart")?;
+                for line in cart {
+                    write!(f, " {}", line)?;
+                }
+                Ok(())
+            }
+            Step::ExpectTotal(total) => write!(f, "expect total {}", total),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scenario {
+    // Each step along with the (1-based) line it's on.
+    pub steps: Vec<(usize, Step)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+// A line item as far as a scenario is concerned, which only cares about what's
+// in the cart and not about the details of the products.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CartLine {
+    pub sku: Sku,
+    pub quantity: u32,
+}
+
+impl From<&LineItem> for CartLine {
+    fn from(line: &LineItem) -> Self {
+        CartLine {
+            sku: line.product.sku.clone(),
+            quantity: line.quantity.get(),
+        }
+    }
+}
+
+impl fmt::Display for CartLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.quantity {
+            1 => write!(f, "{}", self.sku),
+            quantity => write!(f, "{}:{}", self.sku, quantity),
+        }
+    }
+}
+
+fn cart_lines(customer: &AnyCustomer) -> Vec<CartLine> {
+    customer
+        .shopping_cart()
+        .iter()
+        .map(CartLine::from)
+        .collect()
+}
+
+impl Scenario {
+    // Products are added by SKU, so they're looked up in the catalogue.
+    pub fn parse(source: &str, catalogue: &Catalogue) -> Result<Self, ParseError> {
+        let mut steps = vec![];
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let step = parse_step(line, catalogue).map_err(|message| ParseError {
+                line: line_number,
+                message,
+            })?;
+            steps.push((line_number, step));
+        }
+        Ok(Scenario { steps })
+    }
+
+    // Drives a fresh customer of the shop through the steps, stopping at the
+    // first one that's illegal, rejected, or whose expectation isn't met.
+    pub fn run(&self, shop: &Shop) -> Report {
+        let customer = Customer::visit_shop(shop, CustomerId::guest(), NoopSink);
+        let mut customer = AnyCustomer::from(customer);
+        let mut steps_run = 0;
+        let mut failure = None;
+
+        for (line, step) in &self.steps {
+            let failed = match step {
+                Step::Do(action) => match customer.apply(action.clone()) {
+                    Ok(next) => {
+                        customer = next;
+                        None
+                    }
+                    Err(ApplyError::Rejected {
+                        customer: unchanged,
+                        reason,
+                    }) => {
+                        customer = unchanged;
+                        let kind = FailureKind::Rejected {
+                            state: customer.state_name().to_string(),
+                        };
+                        Some((kind, reason.to_string()))
+                    }
+                    Err(ApplyError::Invalid {
+                        customer: unchanged,
+                        err,
+                    }) => {
+                        customer = unchanged;
+                        let kind = FailureKind::IllegalAction {
+                            state: err.from.to_string(),
+                        };
+                        Some((kind, err.to_string()))
+                    }
+                },
+                Step::ExpectState(expected) if expected != customer.state_name() => {
+                    mismatch(FailureKind::UnexpectedState {
+                        expected: expected.clone(),
+                        actual: customer.state_name().to_string(),
+                    })
+                }
+                Step::ExpectCart(expected) if *expected != cart_lines(&customer) => {
+                    mismatch(FailureKind::UnexpectedCart {
+                        expected: expected.clone(),
+                        actual: cart_lines(&customer),
+                    })
+                }
+                Step::ExpectTotal(expected) if customer.total().ok() != Some(*expected) => {
+                    mismatch(FailureKind::UnexpectedTotal {
+                        expected: *expected,
+                        actual: customer.total().ok(),
+                    })
+                }
+                Step::ExpectState(_) | Step::ExpectCart(_) | Step::ExpectTotal(_) => None,
+            };
+
+            if let Some((kind, message)) = failed {
+                failure = Some(Failure {
+                    line: *line,
+                    step: step.to_string(),
+                    message,
+                    kind,
+                });
+                break;
+            }
+            steps_run += 1;
+        }
+
+        Report {
+            passed: failure.is_none(),
+            steps_run,
+            final_state: customer.state_name().to_string(),
+            final_cart: cart_lines(&customer),
+            failure,
+        }
+    }
+}
+
+fn mismatch(kind: FailureKind) -> Option<(FailureKind, String)> {
+    let message = kind.to_string();
+    Some((kind, message))
+}
+
+fn parse_step(line: &str, catalogue: &Catalogue) -> Result<Step, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        ["expect", "state", state] => Ok(Step::ExpectState(state.to_string())),
+        ["expect", "cart", lines @ ..] => lines
+            .iter()
+            .map(|line| parse_cart_line(line))
+            .collect::<Result<_, _>>()
+            .map(Step::ExpectCart),
+        ["expect", "total", amount @ ..] => amount
+            .join(" ")
+            .parse()
+            .map(Step::ExpectTotal)
+            .map_err(|err: ParseMoneyError| err.to_string()),
+        ["expect", ..] => Err(format!(
+            "unknown expectation `{}`, expected `expect state <state>`, `expect cart <sku[:quantity]...>` or `expect total <amount>`",
+            line
+        )),
+        _ => Action::parse(line, catalogue)
+            .map(Step::Do)
+            .map_err(|err| err.to_string()),
+    }
+}
+
+fn parse_cart_line(line: &str) -> Result<CartLine, String> {
+    let (sku, quantity) = match line.split_once(':') {
+        Some((sku, quantity)) => match quantity.parse() {
+            Ok(quantity) if quantity > 0 => (sku, quantity),
+            _ => return Err(format!("`{}` isn't a valid cart line", line)),
+        },
+        None => (line, 1),
+    };
+    Ok(CartLine {
+        sku: Sku::from(sku),
+        quantity,
+    })
+}
+
+// The outcome of running a scenario, which serializes to the JSON report of
+// `stated run --json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Report {
+    pub passed: bool,
+    pub steps_run: usize,
+    pub final_state: String,
+    pub final_cart: Vec<CartLine>,
+    pub failure: Option<Failure>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Failure {
+    pub line: usize,
+    pub step: String,
+    pub message: String,
+    #[serde(flatten)]
+    pub kind: FailureKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum FailureKind {
+    IllegalAction {
+        state: String,
+    },
+    Rejected {
+        state: String,
+    },
+    UnexpectedState {
+        expected: String,
+        actual: String,
+    },
+    UnexpectedCart {
+        expected: Vec<CartLine>,
+        actual: Vec<CartLine>,
+    },
+    // `actual` is `None` if the total can't be computed, e.g. on overflow.
+    UnexpectedTotal {
+        expected: Money<StoreCurrency>,
+        actual: Option<Money<StoreCurrency>>,
+    },
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailureKind::IllegalAction { state } => write!(f, "illegal while {}", state),
+            FailureKind::Rejected { state } => write!(f, "rejected while {}", state),
+            FailureKind::UnexpectedState { expected, actual } => {
+                write!(f, "expected state {}, but was {}", expected, actual)
+            }
+            FailureKind::UnexpectedCart { expected, actual } => {
+                write!(
+                    f,
+                    "expected cart [{}], but was [{}]",
+                    join(expected),
+                    join(actual)
+                )
+            }
+            FailureKind::UnexpectedTotal { expected, actual } => match actual {
+                Some(actual) => write!(f, "expected total {}, but was {}", expected, actual),
+                None => write!(
+                    f,
+                    "expected total {}, but it couldn't be computed",
+                    expected
+                ),
+            },
+        }
+    }
+}
+
+fn join(cart: &[CartLine]) -> String {
+    cart.iter()
+        .map(CartLine::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.failure {
+            None => write!(
+                f,
+                "passed: {} steps, ended in {}",
+                self.steps_run, self.final_state
+            ),
+            Some(failure) => write!(
+                f,
+                "failed at line {} (`{}`): {}",
+                failure.line, failure.step, failure.message
+            ),
+        }
+    }
+}
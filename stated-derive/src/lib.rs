@@ -0,0 +1,353 @@
+// Attribute macros generating the typestate plumbing that `stated::online_shop`
+// writes by hand: the state markers, a sealed trait bounding the entity's state
+// parameter, the `PhantomData` field and the field-moving transitions.
+//
+// `#[typestate(states(...))]` goes on the entity struct, and a bare
+// `#[typestate]` goes on an `impl` block of that entity, whose methods can be
+// marked with `#[transition(From -> To)]`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote, quote_spanned};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{
+    parenthesized, parse_macro_input, Error, Field, Fields, FnArg, Ident, ImplItem, ImplItemFn,
+    Item, ItemImpl, ItemStruct, Result, ReturnType, Token, Type,
+};
+
+// The name of the hidden field holding the state marker.
+const STATE_FIELD: &str = "_state";
+
+#[proc_macro_attribute]
+pub fn typestate(args: TokenStream, input: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(input as Item);
+    let expanded = match item {
+        Item::Struct(item) if args.is_empty() => Err(Error::new(
+            item.ident.span(),
+            "expected the states to be declared, as in `#[typestate(states(...))]`",
+        )),
+        Item::Struct(item) => {
+            let args = parse_macro_input!(args as StateList);
+            expand_struct(args, item)
+        }
+        Item::Impl(item) => {
+            let args = TokenStream2::from(args);
+            if args.is_empty() {
+                expand_impl(item)
+            } else {
+                Err(Error::new(
+                    args.span(),
+                    "`#[typestate]` takes no arguments on an `impl` block, the states \
+                     are declared on the struct",
+                ))
+            }
+        }
+        other => Err(Error::new(
+            other.span(),
+            "`#[typestate]` can only be used on a struct or on an `impl` block of one",
+        )),
+    };
+    expanded.unwrap_or_else(Error::into_compile_error).into()
+}
+
+// This is only ever meaningful inside a `#[typestate]` impl block, which strips
+// it from the methods before the compiler gets to see it.
+#[proc_macro_attribute]
+pub fn transition(_args: TokenStream, input: TokenStream) -> TokenStream {
+    let input = TokenStream2::from(input);
+    let error = Error::new(
+        input.span(),
+        "`#[transition]` can only be used on a method inside a `#[typestate]` impl block",
+    )
+    .into_compile_error();
+    quote!(#error #input).into()
+}
+
+// `states(A, B, C)`
+struct StateList {
+    states: Vec<Ident>,
+}
+
+impl Parse for StateList {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let keyword: Ident = input.parse()?;
+        if keyword != "states" {
+            return Err(Error::new(keyword.span(), "expected `states(...)`"));
+        }
+
+        let content;
+        parenthesized!(content in input);
+        let states: Punctuated<Ident, Token![,]> =
+            content.parse_terminated(Ident::parse, Token![,])?;
+        if states.is_empty() {
+            return Err(Error::new(
+                keyword.span(),
+                "at least one state must be declared",
+            ));
+        }
+
+        let mut seen: Vec<&Ident> = vec![];
+        for state in &states {
+            if seen.contains(&state) {
+                return Err(Error::new(
+                    state.span(),
+                    format!("state `{}` is declared more than once", state),
+                ));
+            }
+            seen.push(state);
+        }
+
+        Ok(StateList {
+            states: states.into_iter().collect(),
+        })
+    }
+}
+
+// `From -> To`
+struct TransitionArgs {
+    from: Ident,
+    to: Ident,
+}
+
+impl Parse for TransitionArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let from = input.parse()?;
+        input.parse::<Token![->]>()?;
+        let to = input.parse()?;
+        Ok(TransitionArgs { from, to })
+    }
+}
+
+fn state_trait_name(entity: &Ident) -> Ident {
+    format_ident!("{}State", entity)
+}
+
+fn expand_struct(args: StateList, item: ItemStruct) -> Result<TokenStream2> {
+    if !item.generics.params.is_empty() {
+        return Err(Error::new(
+            item.generics.span(),
+            "`#[typestate]` structs can't have generic parameters, the state parameter is added for you",
+        ));
+    }
+    let fields: Vec<Field> = match &item.fields {
+        Fields::Named(fields) => fields.named.iter().cloned().collect(),
+        Fields::Unit => vec![],
+        Fields::Unnamed(fields) => {
+            return Err(Error::new(
+                fields.span(),
+                "`#[typestate]` structs must have named fields",
+            ))
+        }
+    };
+    if let Some(field) = fields.iter().find(|field| {
+        field
+            .ident
+            .as_ref()
+            .is_some_and(|ident| ident == STATE_FIELD)
+    }) {
+        return Err(Error::new(
+            field.span(),
+            format!("`{}` is reserved for the state marker", STATE_FIELD),
+        ));
+    }
+
+    let ItemStruct {
+        attrs, vis, ident, ..
+    } = &item;
+    let states = &args.states;
+    let state_names = states.iter().map(Ident::to_string);
+    let state_trait = state_trait_name(ident);
+    let sealed = format_ident!("__{}_sealed", ident.to_string().to_lowercase());
+    let state_field = format_ident!("{}", STATE_FIELD);
+    let field_names: Vec<_> = fields.iter().map(|field| &field.ident).collect();
+    let field_types = fields.iter().map(|field| &field.ty);
+
+    let message = format!("`{{Self}}` is not a state of `{}`", ident);
+    let note = format!(
+        "the states declared on `{}` are: {}",
+        ident,
+        states
+            .iter()
+            .map(|state| format!("`{}`", state))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    Ok(quote! {
+        #(#vis struct #states;)*
+
+        #[doc(hidden)]
+        mod #sealed {
+            pub trait Sealed {}
+        }
+
+        #[diagnostic::on_unimplemented(message = #message, note = #note)]
+        #vis trait #state_trait: #sealed::Sealed {
+            const NAME: &'static str;
+        }
+
+        #(
+            impl #sealed::Sealed for #states {}
+
+            impl #state_trait for #states {
+                const NAME: &'static str = #state_names;
+            }
+        )*
+
+        #(#attrs)*
+        #vis struct #ident<S: #state_trait> {
+            #(#fields,)*
+            #state_field: ::core::marker::PhantomData<S>,
+        }
+
+        #[allow(dead_code)]
+        impl<S: #state_trait> #ident<S> {
+            fn from_parts(#(#field_names: #field_types),*) -> Self {
+                #ident {
+                    #(#field_names,)*
+                    #state_field: ::core::marker::PhantomData,
+                }
+            }
+
+            fn transition<T: #state_trait>(self) -> #ident<T> {
+                #ident {
+                    #(#field_names: self.#field_names,)*
+                    #state_field: ::core::marker::PhantomData,
+                }
+            }
+        }
+    })
+}
+
+fn expand_impl(item: ItemImpl) -> Result<TokenStream2> {
+    if let Some((_, path, _)) = &item.trait_ {
+        return Err(Error::new(
+            path.span(),
+            "`#[typestate]` can't be used on trait impls",
+        ));
+    }
+    if !item.generics.params.is_empty() {
+        return Err(Error::new(
+            item.generics.span(),
+            "`#[typestate]` impl blocks can't have generic parameters",
+        ));
+    }
+    let entity = match &*item.self_ty {
+        Type::Path(path) if path.qself.is_none() => path.path.get_ident().cloned(),
+        _ => None,
+    }
+    .ok_or_else(|| {
+        Error::new(
+            item.self_ty.span(),
+            "expected the bare name of a `#[typestate]` struct",
+        )
+    })?;
+    let state_trait = state_trait_name(&entity);
+
+    let mut shared = vec![];
+    let mut transitions = vec![];
+    for impl_item in item.items {
+        match impl_item {
+            ImplItem::Fn(mut method) => match take_transition_attr(&mut method)? {
+                Some(args) => transitions.push(expand_transition(&entity, args, method)?),
+                None => shared.push(ImplItem::Fn(method)),
+            },
+            other => shared.push(other),
+        }
+    }
+
+    let attrs = &item.attrs;
+    Ok(quote! {
+        #(#attrs)*
+        impl<S: #state_trait> #entity<S> {
+            #(#shared)*
+        }
+
+        #(#transitions)*
+    })
+}
+
+fn take_transition_attr(method: &mut ImplItemFn) -> Result<Option<TransitionArgs>> {
+    let mut found = None;
+    let mut error: Option<Error> = None;
+    method.attrs.retain(|attr| {
+        if !attr.path().is_ident("transition") {
+            return true;
+        }
+        let parsed = if found.is_some() {
+            Err(Error::new(
+                attr.span(),
+                "a method can only perform one `#[transition]`",
+            ))
+        } else {
+            attr.parse_args::<TransitionArgs>()
+        };
+        match parsed {
+            Ok(args) => found = Some(args),
+            Err(err) => match &mut error {
+                Some(error) => error.combine(err),
+                None => error = Some(err),
+            },
+        }
+        false
+    });
+
+    match error {
+        Some(error) => Err(error),
+        None => Ok(found),
+    }
+}
+
+fn expand_transition(
+    entity: &Ident,
+    args: TransitionArgs,
+    method: ImplItemFn,
+) -> Result<TokenStream2> {
+    let sig = &method.sig;
+    match sig.inputs.first() {
+        Some(FnArg::Receiver(receiver))
+            if receiver.reference.is_none() && receiver.colon_token.is_none() => {}
+        _ => {
+            return Err(Error::new(
+                sig.ident.span(),
+                "transition methods must take `self` by value, so the old state can't be used afterwards",
+            ))
+        }
+    }
+    if let ReturnType::Type(_, ty) = &sig.output {
+        return Err(Error::new(
+            ty.span(),
+            "transition methods can't declare a return type, they return the entity in its new state",
+        ));
+    }
+
+    let TransitionArgs { from, to } = args;
+    let ImplItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+        ..
+    } = method;
+    let ident = &sig.ident;
+    let generics = &sig.generics;
+    let where_clause = &sig.generics.where_clause;
+    let inputs = &sig.inputs;
+    // Spanned so that naming something other than a declared state is
+    // reported on the state in the attribute, rather than on the whole impl.
+    let next_entity = Ident::new(&entity.to_string(), to.span());
+    let next = quote_spanned!(to.span()=> #next_entity<#to>);
+    let finish = quote_spanned!(to.span()=> self.transition());
+
+    Ok(quote! {
+        impl #entity<#from> {
+            #(#attrs)*
+            #vis fn #ident #generics(#inputs) -> #next #where_clause {
+                #block
+                #finish
+            }
+        }
+    })
+}
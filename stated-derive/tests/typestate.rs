@@ -0,0 +1,52 @@
+use stated_derive::typestate;
+
+mod shop {
+    use super::typestate;
+
+    #[typestate(states(Browsing, Shopping, Checkout))]
+    pub struct Customer {
+        shopping_cart: Vec<u8>,
+    }
+
+    #[typestate]
+    impl Customer {
+        pub fn shopping_cart(&self) -> &[u8] {
+            &self.shopping_cart
+        }
+
+        #[transition(Browsing -> Shopping)]
+        pub fn add_item(mut self, item: u8) {
+            self.shopping_cart.push(item);
+        }
+
+        #[transition(Shopping -> Checkout)]
+        pub fn proceed_to_checkout(self) {}
+
+        #[transition(Checkout -> Shopping)]
+        pub fn cancel_checkout(self) {}
+    }
+
+    impl Customer<Browsing> {
+        pub fn visit_site() -> Self {
+            Self::from_parts(vec![])
+        }
+    }
+}
+
+use shop::{Browsing, Checkout, Customer, CustomerState, Shopping};
+
+#[test]
+fn transitions_move_the_fields_into_the_next_state() {
+    let checkout: Customer<Checkout> = Customer::visit_site().add_item(42).proceed_to_checkout();
+    assert_eq!(checkout.shopping_cart(), &[42]);
+
+    let shopping: Customer<Shopping> = checkout.cancel_checkout();
+    assert_eq!(shopping.shopping_cart(), &[42]);
+}
+
+#[test]
+fn states_know_their_names() {
+    assert_eq!(Browsing::NAME, "Browsing");
+    assert_eq!(Shopping::NAME, "Shopping");
+    assert_eq!(Checkout::NAME, "Checkout");
+}
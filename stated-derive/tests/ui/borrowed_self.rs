@@ -0,0 +1,12 @@
+use stated_derive::typestate;
+
+#[typestate(states(Browsing, Shopping))]
+struct Customer {}
+
+#[typestate]
+impl Customer {
+    #[transition(Browsing -> Shopping)]
+    fn add_item(&self) {}
+}
+
+fn main() {}
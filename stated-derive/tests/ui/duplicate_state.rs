@@ -0,0 +1,6 @@
+use stated_derive::typestate;
+
+#[typestate(states(Browsing, Shopping, Browsing))]
+struct Customer {}
+
+fn main() {}
@@ -0,0 +1,23 @@
+use stated_derive::typestate;
+
+#[typestate(states(Browsing, Shopping, Checkout))]
+struct Customer {}
+
+#[typestate]
+impl Customer {
+    #[transition(Browsing -> Shopping)]
+    fn add_item(self) {}
+
+    #[transition(Shopping -> Checkout)]
+    fn proceed_to_checkout(self) {}
+}
+
+impl Customer<Browsing> {
+    fn visit_site() -> Self {
+        Self::from_parts()
+    }
+}
+
+fn main() {
+    Customer::visit_site().proceed_to_checkout();
+}
@@ -0,0 +1,6 @@
+use stated_derive::typestate;
+
+#[typestate]
+struct Customer {}
+
+fn main() {}
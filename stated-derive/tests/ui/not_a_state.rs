@@ -0,0 +1,14 @@
+use stated_derive::typestate;
+
+struct Paid;
+
+#[typestate(states(Browsing, Shopping))]
+struct Customer {}
+
+#[typestate]
+impl Customer {
+    #[transition(Shopping -> Paid)]
+    fn finalise_payment(self) {}
+}
+
+fn main() {}
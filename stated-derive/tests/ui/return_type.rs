@@ -0,0 +1,14 @@
+use stated_derive::typestate;
+
+#[typestate(states(Browsing, Shopping))]
+struct Customer {}
+
+#[typestate]
+impl Customer {
+    #[transition(Browsing -> Shopping)]
+    fn add_item(self) -> Customer<Shopping> {
+        self.transition()
+    }
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use stated_derive::transition;
+
+struct Customer;
+
+impl Customer {
+    #[transition(Browsing -> Shopping)]
+    fn add_item(self) {}
+}
+
+fn main() {}
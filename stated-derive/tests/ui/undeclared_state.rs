@@ -0,0 +1,12 @@
+use stated_derive::typestate;
+
+#[typestate(states(Browsing, Shopping))]
+struct Customer {}
+
+#[typestate]
+impl Customer {
+    #[transition(Shopping -> Paid)]
+    fn finalise_payment(self) {}
+}
+
+fn main() {}
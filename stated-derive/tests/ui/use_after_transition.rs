@@ -0,0 +1,22 @@
+use stated_derive::typestate;
+
+#[typestate(states(Browsing, Shopping))]
+struct Customer {}
+
+#[typestate]
+impl Customer {
+    #[transition(Browsing -> Shopping)]
+    fn add_item(self) {}
+}
+
+impl Customer<Browsing> {
+    fn visit_site() -> Self {
+        Self::from_parts()
+    }
+}
+
+fn main() {
+    let browsing = Customer::visit_site();
+    let _shopping = browsing.add_item();
+    let _again = browsing.add_item();
+}
@@ -0,0 +1,42 @@
+use stated::online_shop::{display_cart, Action, AnyCustomer, CartOutcome, Customer};
+
+mod common;
+use common::{lamp, tea};
+
+#[test]
+fn popping_takes_from_the_last_line_not_the_last_added_product() {
+    let shopping = Customer::visit_site()
+        .add_item(tea())
+        .add_item(lamp())
+        .add_item(tea());
+    assert_eq!(
+        display_cart(shopping.shopping_cart()).to_string(),
+        "[2 x tea, 1 x lamp]"
+    );
+
+    let shopping = match shopping.pop_item() {
+        CartOutcome::StillShopping(shopping) => shopping,
+        CartOutcome::Emptied(_) => unreachable!(),
+    };
+    assert_eq!(
+        display_cart(shopping.shopping_cart()).to_string(),
+        "[2 x tea]"
+    );
+}
+
+#[test]
+fn popping_the_last_item_goes_back_to_browsing() {
+    let shopping = Customer::visit_site().add_item(lamp());
+    let browsing = match shopping.pop_item() {
+        CartOutcome::Emptied(browsing) => browsing,
+        CartOutcome::StillShopping(_) => unreachable!(),
+    };
+    assert_eq!(browsing.state_name(), "Browsing");
+    assert!(browsing.shopping_cart().is_empty());
+
+    // The same goes for a customer driven at runtime.
+    let shopping = AnyCustomer::from(browsing.add_item(lamp()));
+    let browsing = shopping.apply(Action::PopItem).ok().unwrap();
+    assert_eq!(browsing.state_name(), "Browsing");
+    assert!(browsing.shopping_cart().is_empty());
+}
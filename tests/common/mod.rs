@@ -0,0 +1,64 @@
+// Fixtures shared by the integration tests. Every test file is its own crate
+// and only uses some of them.
+#![allow(dead_code)]
+
+use stated::online_shop::{
+    Browsing, Catalogue, Checkout, Customer, CustomerId, Money, NeedsAddress, NoopSink,
+    PaymentMethod, Product, ReadyToPay, ShippingOption, Shop, Sku, StoreCurrency,
+};
+
+pub fn eur(cents: i64) -> Money<StoreCurrency> {
+    Money::from_minor(cents)
+}
+
+pub fn sku(sku: &str) -> Sku {
+    Sku::from(sku)
+}
+
+// From the demo catalogue, so priced and taxed like in the `stated` binary.
+pub fn product(sku: &str) -> Product {
+    Catalogue::demo().get(&Sku::from(sku)).unwrap().clone()
+}
+
+pub fn lamp() -> Product {
+    product("lamp")
+}
+
+pub fn plant() -> Product {
+    product("plant")
+}
+
+pub fn tea() -> Product {
+    product("tea")
+}
+
+pub fn visit(shop: &Shop, id: &str) -> Customer<Browsing> {
+    Customer::visit_shop(shop, CustomerId::new(id), NoopSink)
+}
+
+// Adds the products to the cart in order, then checks out.
+pub fn checkout(shop: &Shop, id: &str, products: &[Product]) -> Customer<Checkout<NeedsAddress>> {
+    let (first, rest) = products.split_first().unwrap();
+    let shopping = visit(shop, id).add_item(first.clone());
+    rest.iter()
+        .fold(shopping, |shopping, product| {
+            shopping.add_item(product.clone())
+        })
+        .proceed_to_checkout()
+        .ok()
+        .unwrap()
+}
+
+// Fills in the rest of checking out: standard shipping to the region, paid by
+// card.
+pub fn ready_to_pay(
+    customer: Customer<Checkout<NeedsAddress>>,
+    region: &str,
+) -> Customer<Checkout<ReadyToPay>> {
+    customer
+        .ship_to(region.into())
+        .ok()
+        .unwrap()
+        .choose_shipping(ShippingOption::Standard)
+        .choose_payment(PaymentMethod::Card)
+}
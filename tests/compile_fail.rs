@@ -0,0 +1,9 @@
+// Every transition that isn't defined for a state, and every reuse of a
+// customer after a transition has consumed it, must fail to compile. The
+// expected errors live next to each case in `tests/compile_fail/*.stderr`,
+// regenerate them with `TRYBUILD=overwrite cargo test --test compile_fail`.
+#[test]
+fn invalid_transitions_do_not_compile() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/compile_fail/*.rs");
+}
@@ -0,0 +1,9 @@
+// Amounts in different currencies are different types, so they can't be added
+// up by accident.
+use stated::online_shop::{Eur, Money, Usd};
+
+fn main() {
+    let eur: Money<Eur> = Money::from_minor(450);
+    let usd: Money<Usd> = Money::from_minor(450);
+    let _ = eur.checked_add(usd);
+}
@@ -0,0 +1,8 @@
+// `add_item()` consumes the "Browsing" customer, so it can't be used again.
+use stated::online_shop::{Customer, Money, Product};
+
+fn main() {
+    let browsing = Customer::visit_site();
+    let _ = browsing.add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450)));
+    let _ = browsing.add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450)));
+}
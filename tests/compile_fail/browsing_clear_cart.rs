@@ -0,0 +1,7 @@
+// `clear_cart()` isn't a transition of the "Browsing" state.
+use stated::online_shop::Customer;
+
+fn main() {
+    let browsing = Customer::visit_site();
+    browsing.clear_cart();
+}
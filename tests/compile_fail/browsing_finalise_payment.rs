@@ -0,0 +1,7 @@
+// `finalise_payment()` isn't a transition of the "Browsing" state.
+use stated::online_shop::Customer;
+
+fn main() {
+    let browsing = Customer::visit_site();
+    browsing.finalise_payment();
+}
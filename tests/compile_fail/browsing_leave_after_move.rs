@@ -0,0 +1,8 @@
+// `leave()` consumes the "Browsing" customer, so it can't be used again.
+use stated::online_shop::Customer;
+
+fn main() {
+    let browsing = Customer::visit_site();
+    let _ = browsing.leave();
+    let _ = browsing.leave();
+}
@@ -0,0 +1,7 @@
+// `pop_item()` isn't a transition of the "Browsing" state.
+use stated::online_shop::Customer;
+
+fn main() {
+    let browsing = Customer::visit_site();
+    browsing.pop_item();
+}
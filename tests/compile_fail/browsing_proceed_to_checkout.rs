@@ -0,0 +1,7 @@
+// `proceed_to_checkout()` isn't a transition of the "Browsing" state.
+use stated::online_shop::Customer;
+
+fn main() {
+    let browsing = Customer::visit_site();
+    browsing.proceed_to_checkout();
+}
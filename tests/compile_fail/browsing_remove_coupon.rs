@@ -0,0 +1,7 @@
+// `remove_coupon()` isn't a transition of the "Browsing" state.
+use stated::online_shop::Customer;
+
+fn main() {
+    let browsing = Customer::visit_site();
+    browsing.remove_coupon();
+}
@@ -0,0 +1,7 @@
+// `remove_item()` isn't a transition of the "Browsing" state.
+use stated::online_shop::{Customer, Sku};
+
+fn main() {
+    let browsing = Customer::visit_site();
+    browsing.remove_item(&Sku::from("tea"));
+}
@@ -0,0 +1,7 @@
+// `set_quantity()` isn't a transition of the "Browsing" state.
+use stated::online_shop::{Customer, Sku};
+
+fn main() {
+    let browsing = Customer::visit_site();
+    browsing.set_quantity(&Sku::from("tea"), 2);
+}
@@ -0,0 +1,7 @@
+// `ship_to()` isn't a transition of the "Browsing" state.
+use stated::online_shop::{Customer, Region};
+
+fn main() {
+    let browsing = Customer::visit_site();
+    let _ = browsing.ship_to(Region::from("NL"));
+}
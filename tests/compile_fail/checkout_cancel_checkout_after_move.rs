@@ -0,0 +1,8 @@
+// `cancel_checkout()` consumes the "Checkout" customer, so it can't be used again.
+use stated::online_shop::{Customer, Money, Product};
+
+fn main() {
+    let checkout = Customer::visit_site().add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450))).proceed_to_checkout().ok().unwrap();
+    let _ = checkout.cancel_checkout();
+    let _ = checkout.cancel_checkout();
+}
@@ -0,0 +1,8 @@
+// `finalise_payment()` consumes the "ReadyToPay" customer, so it can't be used again.
+use stated::online_shop::{Customer, MockGateway, Money, PaymentMethod, Product, Region, ShippingOption};
+
+fn main() {
+    let checkout = Customer::visit_site().add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450))).proceed_to_checkout().ok().unwrap().ship_to(Region::from("NL")).ok().unwrap().choose_shipping(ShippingOption::Standard).choose_payment(PaymentMethod::Card);
+    let _ = checkout.finalise_payment(&MockGateway::new());
+    let _ = checkout.finalise_payment(&MockGateway::new());
+}
@@ -0,0 +1,7 @@
+// `pop_item()` isn't a transition of the "Checkout" state.
+use stated::online_shop::{Customer, Money, Product};
+
+fn main() {
+    let checkout = Customer::visit_site().add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450))).proceed_to_checkout().ok().unwrap();
+    checkout.pop_item();
+}
@@ -0,0 +1,11 @@
+// `set_quantity()` isn't a transition of the "Checkout" state.
+use stated::online_shop::{Customer, Money, Product, Sku};
+
+fn main() {
+    let checkout = Customer::visit_site()
+        .add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450)))
+        .proceed_to_checkout()
+        .ok()
+        .unwrap();
+    checkout.set_quantity(&Sku::from("tea"), 2);
+}
@@ -0,0 +1,8 @@
+// `ship_to()` consumes the "Checkout" customer, so it can't be used again.
+use stated::online_shop::{Customer, Money, Product, Region};
+
+fn main() {
+    let checkout = Customer::visit_site().add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450))).proceed_to_checkout().ok().unwrap();
+    let _ = checkout.ship_to(Region::from("NL"));
+    let _ = checkout.ship_to(Region::from("NL"));
+}
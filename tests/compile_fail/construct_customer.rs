@@ -0,0 +1,11 @@
+// The fields are private, so `visit_site()` is the only way into the flow.
+use std::marker::PhantomData;
+
+use stated::online_shop::{Checkout, Customer, LineItem, Money, NeedsAddress, NonEmpty, Product};
+
+fn main() {
+    let _checkout: Customer<Checkout<NeedsAddress>> = Customer {
+        shopping_cart: NonEmpty::new(LineItem::new(Product::new("tea", "Loose leaf tea", Money::from_minor(450)))),
+        _inner: PhantomData,
+    };
+}
@@ -0,0 +1,9 @@
+// The fields can't be set from outside the shop, so `finalise_payment()` is the only way to get an order.
+use stated::online_shop::{Delivered, Order, OrderId, Timestamp};
+
+fn main() {
+    let _delivered: Order<Delivered> = Order {
+        id: OrderId::new("order-1"),
+        state: Delivered { delivered_at: Timestamp::from_unix(0) },
+    };
+}
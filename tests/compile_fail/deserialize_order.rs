@@ -0,0 +1,6 @@
+// Orders can be written out but not read back in, or any JSON could be made into a delivered order.
+use stated::online_shop::{Delivered, Order};
+
+fn main() {
+    let _delivered: Order<Delivered> = serde_json::from_str("{}").unwrap();
+}
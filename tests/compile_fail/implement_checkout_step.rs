@@ -0,0 +1,11 @@
+// `CheckoutStep` is sealed too, so no steps can be added outside the crate.
+use stated::online_shop::{CheckoutStep, Transition};
+
+struct NeedsGiftWrap;
+
+impl CheckoutStep for NeedsGiftWrap {
+    const NAME: &'static str = "NeedsGiftWrap";
+    const TRANSITIONS: &'static [Transition] = &[];
+}
+
+fn main() {}
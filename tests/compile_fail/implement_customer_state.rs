@@ -0,0 +1,12 @@
+// `CustomerState` is sealed, so it can't be implemented outside the crate.
+use stated::online_shop::{CustomerState, Transition};
+
+struct Paid;
+
+impl CustomerState for Paid {
+    const NAME: &'static str = "Paid";
+    const IS_TERMINAL: bool = true;
+    const TRANSITIONS: &'static [Transition] = &[];
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+// The steps of checking out can't be skipped: the address comes first.
+use stated::online_shop::{Customer, Money, Product, ShippingOption};
+
+fn main() {
+    let checkout = Customer::visit_site().add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450))).proceed_to_checkout().ok().unwrap();
+    let _ = checkout.choose_shipping(ShippingOption::Standard);
+}
@@ -0,0 +1,7 @@
+// `finalise_payment()` only exists once the whole checkout form is filled in.
+use stated::online_shop::{Customer, MockGateway, Money, Product};
+
+fn main() {
+    let checkout = Customer::visit_site().add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450))).proceed_to_checkout().ok().unwrap();
+    let _ = checkout.finalise_payment(&MockGateway::new());
+}
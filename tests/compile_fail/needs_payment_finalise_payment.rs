@@ -0,0 +1,7 @@
+// Paying needs a payment method, even once the address and shipping are known.
+use stated::online_shop::{Customer, MockGateway, Money, Product, Region, ShippingOption};
+
+fn main() {
+    let checkout = Customer::visit_site().add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450))).proceed_to_checkout().ok().unwrap().ship_to(Region::from("NL")).ok().unwrap().choose_shipping(ShippingOption::Express);
+    let _ = checkout.finalise_payment(&MockGateway::new());
+}
@@ -0,0 +1,6 @@
+// Only the state markers are states, so this type can't even be named.
+use stated::online_shop::Customer;
+
+fn main() {
+    let _customer: Option<Customer<String>> = None;
+}
@@ -0,0 +1,10 @@
+// `ship()` consumes the "Packed" order, so the same parcel can't be shipped twice.
+use stated::online_shop::{Customer, MockGateway, Money, PaymentMethod, Product, ShippingOption};
+
+fn main() {
+    let checkout = Customer::visit_site().add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450))).proceed_to_checkout().ok().unwrap()
+        .ship_to("DE".into()).ok().unwrap().choose_shipping(ShippingOption::Standard).choose_payment(PaymentMethod::Card);
+    let packed = checkout.finalise_payment(&MockGateway::new()).ok().unwrap().pack();
+    let _ = packed.ship("TRACK-123");
+    let _ = packed.ship("TRACK-456");
+}
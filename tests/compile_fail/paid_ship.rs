@@ -0,0 +1,9 @@
+// An order has to be packed before it can be shipped.
+use stated::online_shop::{Customer, MockGateway, Money, PaymentMethod, Product, ShippingOption};
+
+fn main() {
+    let checkout = Customer::visit_site().add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450))).proceed_to_checkout().ok().unwrap()
+        .ship_to("DE".into()).ok().unwrap().choose_shipping(ShippingOption::Standard).choose_payment(PaymentMethod::Card);
+    let paid = checkout.finalise_payment(&MockGateway::new()).ok().unwrap();
+    paid.ship("TRACK-123");
+}
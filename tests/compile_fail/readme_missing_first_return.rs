@@ -0,0 +1,14 @@
+// From the readme: removing the first `return;` lets `browsing` be used after
+// `leave()` has consumed it.
+use stated::online_shop::{Customer, Money, Product};
+
+fn main() {
+    let has_sudden_change_of_plan = false;
+
+    let browsing = Customer::visit_site();
+    if has_sudden_change_of_plan {
+        browsing.leave();
+    }
+
+    let _shopping = browsing.add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450)));
+}
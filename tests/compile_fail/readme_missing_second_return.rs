@@ -0,0 +1,15 @@
+// From the readme: removing the second `return;` lets `shopping` be used after
+// `clear_cart()` has consumed it.
+use stated::online_shop::{Customer, Money, Product};
+
+fn main() {
+    let is_using_mums_credit_card = false;
+
+    let shopping = Customer::visit_site().add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450)));
+    if is_using_mums_credit_card {
+        let browsing = shopping.clear_cart();
+        browsing.leave();
+    }
+
+    let _checkout = shopping.proceed_to_checkout();
+}
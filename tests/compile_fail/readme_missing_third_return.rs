@@ -0,0 +1,16 @@
+// From the readme: removing the last `return;` lets `checkout` be used after
+// `cancel_checkout()` has consumed it.
+use stated::online_shop::{Customer, Money, Product, Region};
+
+fn main() {
+    let forgot_my_wallet = false;
+
+    let checkout = Customer::visit_site().add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450))).proceed_to_checkout().ok().unwrap();
+    if forgot_my_wallet {
+        let shopping = checkout.cancel_checkout();
+        let browsing = shopping.clear_cart();
+        browsing.leave();
+    }
+
+    let _ = checkout.ship_to(Region::from("NL"));
+}
@@ -0,0 +1,13 @@
+// Nothing is refunded until the return has been received.
+use std::num::NonZeroU32;
+
+use stated::online_shop::{Customer, MockGateway, Money, PaymentMethod, Product, ShippingOption, ReturnLine, ReturnPolicy, Timestamp};
+
+fn main() {
+    let gateway = MockGateway::new();
+    let checkout = Customer::visit_site().add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450))).proceed_to_checkout().ok().unwrap()
+        .ship_to("DE".into()).ok().unwrap().choose_shipping(ShippingOption::Standard).choose_payment(PaymentMethod::Card);
+    let delivered = checkout.finalise_payment(&gateway).ok().unwrap().pack().ship("TRACK-123").deliver(Timestamp::from_unix(0));
+    let requested = delivered.request_return(&ReturnPolicy::default(), vec![ReturnLine::new("tea", NonZeroU32::MIN)], "changed my mind").ok().unwrap();
+    let _ = requested.refund(&gateway, vec![]);
+}
@@ -0,0 +1,11 @@
+// `cancel()` isn't a transition of the "Shipped" state, it's too late by then.
+use stated::online_shop::{Customer, MockGateway, Money, PaymentMethod, Product, ShippingOption};
+
+fn main() {
+    let gateway = MockGateway::new();
+    let checkout = Customer::visit_site().add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450))).proceed_to_checkout().ok().unwrap()
+        .ship_to("DE".into()).ok().unwrap().choose_shipping(ShippingOption::Standard).choose_payment(PaymentMethod::Card);
+    let paid = checkout.finalise_payment(&gateway).ok().unwrap();
+    let shipped = paid.pack().ship("TRACK-123");
+    let _ = shipped.cancel(&gateway, &stated::online_shop::InMemoryInventory::default());
+}
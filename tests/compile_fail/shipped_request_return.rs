@@ -0,0 +1,11 @@
+// An order can only be returned once it's been delivered.
+use std::num::NonZeroU32;
+
+use stated::online_shop::{Customer, MockGateway, Money, PaymentMethod, Product, ShippingOption, ReturnLine, ReturnPolicy};
+
+fn main() {
+    let checkout = Customer::visit_site().add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450))).proceed_to_checkout().ok().unwrap()
+        .ship_to("DE".into()).ok().unwrap().choose_shipping(ShippingOption::Standard).choose_payment(PaymentMethod::Card);
+    let shipped = checkout.finalise_payment(&MockGateway::new()).ok().unwrap().pack().ship("TRACK-123");
+    let _ = shipped.request_return(&ReturnPolicy::default(), vec![ReturnLine::new("tea", NonZeroU32::MIN)], "changed my mind");
+}
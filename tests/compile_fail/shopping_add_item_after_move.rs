@@ -0,0 +1,8 @@
+// `add_item()` consumes the "Shopping" customer, so it can't be used again.
+use stated::online_shop::{Customer, Money, Product};
+
+fn main() {
+    let shopping = Customer::visit_site().add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450)));
+    let _ = shopping.add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450)));
+    let _ = shopping.add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450)));
+}
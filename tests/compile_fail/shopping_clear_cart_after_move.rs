@@ -0,0 +1,8 @@
+// `clear_cart()` consumes the "Shopping" customer, so it can't be used again.
+use stated::online_shop::{Customer, Money, Product};
+
+fn main() {
+    let shopping = Customer::visit_site().add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450)));
+    let _ = shopping.clear_cart();
+    let _ = shopping.clear_cart();
+}
@@ -0,0 +1,8 @@
+// Popping an item might empty the cart, so there's no checking out until it's
+// known whether the customer is still shopping.
+use stated::online_shop::{Customer, Money, Product};
+
+fn main() {
+    let shopping = Customer::visit_site().add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450)));
+    shopping.pop_item().proceed_to_checkout();
+}
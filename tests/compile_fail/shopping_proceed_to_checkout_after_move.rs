@@ -0,0 +1,8 @@
+// `proceed_to_checkout()` consumes the "Shopping" customer, so it can't be used again.
+use stated::online_shop::{Customer, Money, Product};
+
+fn main() {
+    let shopping = Customer::visit_site().add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450)));
+    let _ = shopping.proceed_to_checkout();
+    let _ = shopping.proceed_to_checkout();
+}
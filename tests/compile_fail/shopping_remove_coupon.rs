@@ -0,0 +1,7 @@
+// `remove_coupon()` isn't a transition of the "Shopping" state.
+use stated::online_shop::{Customer, Money, Product};
+
+fn main() {
+    let shopping = Customer::visit_site().add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450)));
+    shopping.remove_coupon();
+}
@@ -0,0 +1,8 @@
+// `remove_item()` consumes the "Shopping" customer, so it can't be used again.
+use stated::online_shop::{Customer, Money, Product, Sku};
+
+fn main() {
+    let shopping = Customer::visit_site().add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450)));
+    let _ = shopping.remove_item(&Sku::from("tea"));
+    let _ = shopping.remove_item(&Sku::from("tea"));
+}
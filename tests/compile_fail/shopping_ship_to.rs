@@ -0,0 +1,7 @@
+// `ship_to()` isn't a transition of the "Shopping" state.
+use stated::online_shop::{Customer, Money, Product, Region};
+
+fn main() {
+    let shopping = Customer::visit_site().add_item(Product::new("tea", "Loose leaf tea", Money::from_minor(450)));
+    let _ = shopping.ship_to(Region::from("NL"));
+}
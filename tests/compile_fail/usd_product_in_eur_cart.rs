@@ -0,0 +1,8 @@
+// The store sells in euros, so a product priced in dollars can't be put in
+// the cart without converting its price first.
+use stated::online_shop::{Customer, Money, Product, Usd};
+
+fn main() {
+    let mug: Product<Usd> = Product::new("mug", "Enamel mug", Money::from_minor(1300));
+    Customer::visit_site().add_item(mug);
+}
@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+use stated::online_shop::{
+    Action, AnyCustomer, ApplyError, InMemoryInventory, InvalidTransition, Inventory, Shop,
+    SystemClock,
+};
+
+mod common;
+use common::{checkout, lamp, sku};
+
+#[test]
+fn invalid_actions_hand_the_customer_back() {
+    let inventory = InMemoryInventory::new(SystemClock)
+        .with_ttl(Duration::from_secs(600))
+        .with_stock("lamp", 1);
+    let shop = Shop::new().with_inventory(inventory.clone());
+    let customer = AnyCustomer::from(checkout(&shop, "alice", &[lamp()]));
+    assert_eq!(inventory.available(&sku("lamp")), Some(0));
+
+    let customer = match customer.apply(Action::PopItem) {
+        Err(ApplyError::Invalid { customer, err }) => {
+            assert_eq!(
+                err,
+                InvalidTransition {
+                    from: "NeedsAddress",
+                    action: Action::PopItem,
+                }
+            );
+            customer
+        }
+        other => panic!("expected pop_item to be invalid, got {:?}", other.err()),
+    };
+    assert_eq!(customer.state_name(), "NeedsAddress");
+    assert_eq!(customer.shopping_cart().len(), 1);
+    assert_eq!(inventory.available(&sku("lamp")), Some(0));
+
+    // The reservation is still the customer's to release.
+    let customer = customer.apply(Action::CancelCheckout).ok().unwrap();
+    assert_eq!(customer.state_name(), "Shopping");
+    assert_eq!(inventory.available(&sku("lamp")), Some(1));
+}
+
+#[test]
+fn runtime_customers_only_convert_to_their_own_state() {
+    let checkout = checkout(&Shop::new(), "alice", &[lamp()]);
+
+    let customer = AnyCustomer::from(checkout);
+    let customer = customer.try_into_browsing().err().unwrap();
+    let customer = customer.try_into_shopping().err().unwrap();
+    let customer = customer.try_into_needs_shipping().err().unwrap();
+    let customer = customer.try_into_needs_payment().err().unwrap();
+    let customer = customer.try_into_ready_to_pay().err().unwrap();
+    // Every wrong guess hands back the same customer.
+    assert_eq!(customer.state_name(), "NeedsAddress");
+    assert_eq!(customer.shopping_cart().len(), 1);
+
+    let checkout = customer.try_into_needs_address().ok().unwrap();
+    let shopping = AnyCustomer::from(checkout.cancel_checkout());
+    let shopping = shopping.try_into_needs_address().err().unwrap();
+    assert!(shopping.try_into_shopping().is_ok());
+
+    let left = AnyCustomer::Left(None);
+    let left = left.try_into_browsing().err().unwrap();
+    assert_eq!(left.state_name(), "Left");
+}
@@ -0,0 +1,139 @@
+use stated::online_shop::{
+    Action, AnyCustomer, ApplyError, Customer, CustomerId, LineItem, MockGateway, PaymentMethod,
+    Region, ShippingOption, Shop, TaxTable, TransitionEvent, VecSink,
+};
+
+mod common;
+use common::{lamp, tea};
+
+fn shop() -> Shop {
+    Shop::new().with_taxes(TaxTable::demo())
+}
+
+fn event(
+    from: Option<&'static str>,
+    action: Action,
+    to: &'static str,
+    cart_before: &[LineItem],
+    cart_after: &[LineItem],
+) -> TransitionEvent {
+    TransitionEvent {
+        from,
+        to,
+        action,
+        cart_before: cart_before.to_vec(),
+        cart_after: cart_after.to_vec(),
+    }
+}
+
+#[test]
+fn every_transition_from_browsing_to_paying_is_recorded() {
+    let sink = VecSink::new();
+    Customer::visit_shop(&shop(), CustomerId::new("alice"), sink.clone())
+        .add_item(tea())
+        .add_item(lamp())
+        .proceed_to_checkout()
+        .ok()
+        .unwrap()
+        .ship_to("NL".into())
+        .ok()
+        .unwrap()
+        .choose_shipping(ShippingOption::Standard)
+        .choose_payment(PaymentMethod::Card)
+        .finalise_payment(&MockGateway::new())
+        .ok()
+        .unwrap();
+
+    let just_tea = [LineItem::new(tea())];
+    let cart = [LineItem::new(tea()), LineItem::new(lamp())];
+    assert_eq!(
+        sink.events(),
+        [
+            event(None, Action::VisitSite, "Browsing", &[], &[]),
+            event(
+                Some("Browsing"),
+                Action::AddItem(tea()),
+                "Shopping",
+                &[],
+                &just_tea
+            ),
+            event(
+                Some("Shopping"),
+                Action::AddItem(lamp()),
+                "Shopping",
+                &just_tea,
+                &cart
+            ),
+            event(
+                Some("Shopping"),
+                Action::ProceedToCheckout,
+                "NeedsAddress",
+                &cart,
+                &cart
+            ),
+            event(
+                Some("NeedsAddress"),
+                Action::ShipTo(Region::from("NL")),
+                "NeedsShipping",
+                &cart,
+                &cart
+            ),
+            event(
+                Some("NeedsShipping"),
+                Action::ChooseShipping(ShippingOption::Standard),
+                "NeedsPayment",
+                &cart,
+                &cart
+            ),
+            event(
+                Some("NeedsPayment"),
+                Action::ChoosePayment(PaymentMethod::Card),
+                "ReadyToPay",
+                &cart,
+                &cart
+            ),
+            event(
+                Some("ReadyToPay"),
+                Action::FinalisePayment,
+                "Paid",
+                &cart,
+                &cart
+            ),
+        ]
+    );
+}
+
+#[test]
+fn refused_and_invalid_transitions_are_not_recorded() {
+    let sink = VecSink::new();
+    let checkout = Customer::visit_shop(&shop(), CustomerId::new("alice"), sink.clone())
+        .add_item(tea())
+        .proceed_to_checkout()
+        .ok()
+        .unwrap();
+    assert_eq!(sink.events().len(), 3);
+
+    // There's no tax rule for anywhere outside the EU.
+    let (checkout, _) = checkout.ship_to("US".into()).err().unwrap();
+    assert_eq!(sink.events().len(), 3);
+
+    let customer = match AnyCustomer::from(checkout).apply(Action::PopItem) {
+        Err(ApplyError::Invalid { customer, .. }) => customer,
+        other => panic!("expected pop_item to be invalid, got {:?}", other.err()),
+    };
+    assert_eq!(sink.events().len(), 3);
+
+    // The customer carries on from where they were.
+    customer.apply(Action::CancelCheckout).ok().unwrap();
+    let just_tea = [LineItem::new(tea())];
+    assert_eq!(
+        sink.events().last(),
+        Some(&event(
+            Some("NeedsAddress"),
+            Action::CancelCheckout,
+            "Shopping",
+            &just_tea,
+            &just_tea
+        ))
+    );
+}
@@ -0,0 +1,228 @@
+use stated::online_shop::{
+    graph, order_graph, AbandonedSession, Browsing, Cancelled, CartOutcome, Checkout, CheckoutStep,
+    CouponError, Customer, Delivered, Edge, Inventory, NeedsAddress, NeedsPayment, NeedsShipping,
+    Order, OutOfStock, Packed, Paid, PaymentError, PaymentGateway, PaymentMethod, Product,
+    ReadyToPay, RefundLine, Refunded, Region, ReturnError, ReturnLine, ReturnPolicy,
+    ReturnReceived, ReturnRejected, ReturnRequested, Shipped, ShippingOption, Shopping, Sku,
+    TaxError, Timestamp,
+};
+
+// A transition from `S` to `T` that hands the customer back when it fails.
+type Fallible<S, E, T = Customer<S>> = Result<T, (Customer<S>, E)>;
+
+type Gateway<'a> = &'a dyn PaymentGateway;
+
+type Stock<'a> = &'a dyn Inventory;
+
+// Every transition method, pinned by its signature so that changing where one
+// leads (or removing it) fails to compile until this list is updated, at which
+// point the graph has to agree with it too.
+fn transition_methods() -> Vec<Edge> {
+    type Address = Checkout<NeedsAddress>;
+    type Shipping = Checkout<NeedsShipping>;
+    type Payment = Checkout<NeedsPayment>;
+    type Ready = Checkout<ReadyToPay>;
+    type Couponed<S> = Fallible<Checkout<S>, CouponError>;
+    type Addressed = Fallible<Address, TaxError, Customer<Shipping>>;
+    type Placed = Fallible<Ready, PaymentError, Order<Paid>>;
+
+    // Coupons and cancelling work the same way at every step of checking out.
+    fn every_step<S: CheckoutStep>() {
+        let _: fn(Customer<Checkout<S>>, &str) -> Couponed<S> =
+            Customer::<Checkout<S>>::apply_coupon;
+        let _: fn(Customer<Checkout<S>>) -> Customer<Checkout<S>> =
+            Customer::<Checkout<S>>::remove_coupon;
+        let _: fn(Customer<Checkout<S>>) -> Customer<Shopping> =
+            Customer::<Checkout<S>>::cancel_checkout;
+    }
+
+    let _: fn(Customer<Browsing>) -> Option<AbandonedSession> = Customer::<Browsing>::leave;
+    let _: fn(Customer<Browsing>, Product) -> Customer<Shopping> = Customer::<Browsing>::add_item;
+    let _: fn(Customer<Shopping>, Product) -> Customer<Shopping> = Customer::<Shopping>::add_item;
+    let _: fn(Customer<Shopping>) -> CartOutcome = Customer::<Shopping>::pop_item;
+    let _: fn(Customer<Shopping>, &Sku) -> CartOutcome = Customer::<Shopping>::remove_item;
+    let _: fn(Customer<Shopping>, &Sku, u32) -> CartOutcome = Customer::<Shopping>::set_quantity;
+    let _: fn(Customer<Shopping>) -> Customer<Browsing> = Customer::<Shopping>::clear_cart;
+    let _: fn(Customer<Shopping>) -> Fallible<Shopping, OutOfStock, Customer<Address>> =
+        Customer::<Shopping>::proceed_to_checkout;
+    every_step::<NeedsAddress>();
+    let _: fn(Customer<Address>, Region) -> Addressed = Customer::<Address>::ship_to;
+    every_step::<NeedsShipping>();
+    let _: fn(Customer<Shipping>, ShippingOption) -> Customer<Payment> =
+        Customer::<Shipping>::choose_shipping;
+    every_step::<NeedsPayment>();
+    let _: fn(Customer<Payment>, PaymentMethod) -> Customer<Ready> =
+        Customer::<Payment>::choose_payment;
+    every_step::<ReadyToPay>();
+    let _: fn(Customer<Ready>, PaymentMethod) -> Customer<Ready> =
+        Customer::<Ready>::choose_payment;
+    let _: fn(Customer<Ready>, Gateway) -> Placed = Customer::<Ready>::finalise_payment;
+
+    let mut edges = vec![
+        ("Browsing", "leave", "Left"),
+        ("Browsing", "add_item", "Shopping"),
+        ("Shopping", "add_item", "Shopping"),
+        ("Shopping", "pop_item", "Shopping"),
+        ("Shopping", "pop_item", "Browsing"),
+        ("Shopping", "remove_item", "Shopping"),
+        ("Shopping", "remove_item", "Browsing"),
+        ("Shopping", "set_quantity", "Shopping"),
+        ("Shopping", "set_quantity", "Browsing"),
+        ("Shopping", "clear_cart", "Browsing"),
+        ("Shopping", "proceed_to_checkout", "NeedsAddress"),
+        ("NeedsAddress", "ship_to", "NeedsShipping"),
+        ("NeedsShipping", "choose_shipping", "NeedsPayment"),
+        ("NeedsPayment", "choose_payment", "ReadyToPay"),
+        ("ReadyToPay", "choose_payment", "ReadyToPay"),
+        ("ReadyToPay", "finalise_payment", "Paid"),
+    ];
+    for step in [
+        "NeedsAddress",
+        "NeedsShipping",
+        "NeedsPayment",
+        "ReadyToPay",
+    ] {
+        edges.push((step, "apply_coupon", step));
+        edges.push((step, "remove_coupon", step));
+        edges.push((step, "cancel_checkout", "Shopping"));
+    }
+    edges
+        .into_iter()
+        .map(|(from, method, to)| Edge { from, method, to })
+        .collect()
+}
+
+// The same for the order's transitions, starting from the order that
+// `finalise_payment()` hands over.
+fn order_transition_methods() -> Vec<Edge> {
+    type Refundable<S, E = PaymentError, T = Cancelled> = Result<Order<T>, (Order<S>, E)>;
+    type Returnable = Refundable<Delivered, ReturnError, ReturnRequested>;
+    type Refunding = Refundable<ReturnReceived, ReturnError, Refunded>;
+
+    let _: fn(Order<Paid>) -> Order<Packed> = Order::<Paid>::pack;
+    let _: fn(Order<Paid>, Gateway, Stock) -> Refundable<Paid> = Order::<Paid>::cancel;
+    let _: fn(Order<Packed>, String) -> Order<Shipped> = Order::<Packed>::ship;
+    let _: fn(Order<Packed>, Gateway, Stock) -> Refundable<Packed> = Order::<Packed>::cancel;
+    let _: fn(Order<Shipped>, Timestamp) -> Order<Delivered> = Order::<Shipped>::deliver;
+    let _: fn(Order<Delivered>, &ReturnPolicy, Vec<ReturnLine>, String) -> Returnable =
+        Order::<Delivered>::request_return;
+    let _: fn(Order<ReturnRequested>) -> Order<ReturnReceived> =
+        Order::<ReturnRequested>::receive_return;
+    let _: fn(Order<ReturnReceived>, Gateway, Vec<RefundLine>) -> Refunding =
+        Order::<ReturnReceived>::refund;
+    let _: fn(Order<ReturnReceived>, String) -> Order<ReturnRejected> =
+        Order::<ReturnReceived>::reject_return;
+
+    [
+        ("Paid", "pack", "Packed"),
+        ("Paid", "cancel", "Cancelled"),
+        ("Packed", "ship", "Shipped"),
+        ("Packed", "cancel", "Cancelled"),
+        ("Shipped", "deliver", "Delivered"),
+        ("Delivered", "request_return", "ReturnRequested"),
+        ("ReturnRequested", "receive_return", "ReturnReceived"),
+        ("ReturnReceived", "refund", "Refunded"),
+        ("ReturnReceived", "reject_return", "ReturnRejected"),
+    ]
+    .into_iter()
+    .map(|(from, method, to)| Edge { from, method, to })
+    .collect()
+}
+
+#[test]
+fn graph_matches_the_transition_methods() {
+    let mut expected = transition_methods();
+    let mut actual = graph().edges;
+    expected.sort_by_key(|edge| (edge.from, edge.method, edge.to));
+    actual.sort_by_key(|edge| (edge.from, edge.method, edge.to));
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn graph_has_left_and_paid_as_terminal_states() {
+    let terminals: Vec<_> = graph()
+        .nodes
+        .into_iter()
+        .filter(|node| node.is_terminal)
+        .map(|node| node.name)
+        .collect();
+    assert_eq!(terminals, ["Left", "Paid"]);
+}
+
+#[test]
+fn order_graph_matches_the_transition_methods() {
+    let mut expected = order_transition_methods();
+    let mut actual = order_graph().edges;
+    expected.sort_by_key(|edge| (edge.from, edge.method, edge.to));
+    actual.sort_by_key(|edge| (edge.from, edge.method, edge.to));
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn order_graph_ends_cancelled_or_returned() {
+    let orders = order_graph();
+    let terminals: Vec<_> = orders
+        .nodes
+        .iter()
+        .filter(|node| node.is_terminal)
+        .map(|node| node.name)
+        .collect();
+    assert_eq!(terminals, ["Cancelled", "Refunded", "ReturnRejected"]);
+    // The customer's flow exits into the state orders start in.
+    assert!(graph()
+        .nodes
+        .iter()
+        .any(|node| node.is_terminal && node.name == orders.initial));
+    // Nothing leads out of a terminal state, in either state machine.
+    for graph in [graph(), orders] {
+        for edge in &graph.edges {
+            assert!(
+                !graph
+                    .nodes
+                    .iter()
+                    .any(|node| node.is_terminal && node.name == edge.from),
+                "{} is terminal, but leads to {}",
+                edge.from,
+                edge.to
+            );
+        }
+    }
+}
+
+#[test]
+fn checked_in_diagram_is_up_to_date() {
+    let checked_in = include_str!("../online_store_state_machine.dot");
+    assert_eq!(
+        graph().to_dot(),
+        checked_in,
+        "regenerate it with `cargo run -- graph --format dot > online_store_state_machine.dot`"
+    );
+}
+
+#[test]
+fn mermaid_matches_golden_file() {
+    assert_eq!(graph().to_mermaid(), include_str!("golden/customer.mmd"));
+}
+
+#[test]
+fn plantuml_matches_golden_file() {
+    assert_eq!(graph().to_plantuml(), include_str!("golden/customer.puml"));
+}
+
+#[test]
+fn readme_diagram_is_up_to_date() {
+    let readme = include_str!("../readme.md");
+    assert!(
+        readme.contains(&format!("```mermaid\n{}```", graph().to_mermaid())),
+        "update the readme with the output of `cargo run -- graph --format mermaid`"
+    );
+}
+
+#[test]
+fn readme_order_diagram_is_up_to_date() {
+    let readme = include_str!("../readme.md");
+    assert!(
+        readme.contains(&format!("```mermaid\n{}```", order_graph().to_mermaid())),
+        "update the readme with the output of `cargo run -- graph --order --format mermaid`"
+    );
+}
@@ -0,0 +1,199 @@
+use std::time::Duration;
+
+use stated::online_shop::{
+    CartOutcome, Customer, FixedClock, InMemoryInventory, Inventory, MockGateway, OutOfStock,
+    PaymentError, Shop, TaxTable, Timestamp, Unavailable,
+};
+
+mod common;
+use common::{lamp, plant, ready_to_pay, sku, tea, visit};
+
+fn setup() -> (FixedClock, InMemoryInventory, Shop) {
+    let clock = FixedClock::new(Timestamp::from_unix(1_704_067_200));
+    // Tea isn't given any stock, so isn't tracked.
+    let inventory = InMemoryInventory::new(clock.clone())
+        .with_ttl(Duration::from_secs(600))
+        .with_stock("lamp", 2)
+        .with_stock("plant", 1);
+    let shop = Shop::new()
+        .with_taxes(TaxTable::demo())
+        .with_inventory(inventory.clone());
+    (clock, inventory, shop)
+}
+
+#[test]
+fn checking_out_reserves_the_cart() {
+    let (_, inventory, shop) = setup();
+    let shopping = visit(&shop, "alice").add_item(lamp()).add_item(tea());
+    assert_eq!(inventory.available(&sku("lamp")), Some(2));
+
+    let checkout = shopping.proceed_to_checkout().ok().unwrap();
+    assert_eq!(inventory.available(&sku("lamp")), Some(1));
+    assert_eq!(inventory.available(&sku("tea")), None);
+
+    // Reserved, not sold.
+    assert_eq!(inventory.on_hand(&sku("lamp")), Some(2));
+    ready_to_pay(checkout, "NL")
+        .finalise_payment(&MockGateway::new())
+        .ok()
+        .unwrap();
+    assert_eq!(inventory.on_hand(&sku("lamp")), Some(1));
+    assert_eq!(inventory.available(&sku("lamp")), Some(1));
+}
+
+#[test]
+fn short_stock_hands_the_shopping_customer_back() {
+    let (_, inventory, shop) = setup();
+    let shopping = visit(&shop, "alice")
+        .add_item(lamp())
+        .add_item(lamp())
+        .add_item(lamp())
+        .add_item(plant())
+        .add_item(tea());
+
+    let (shopping, err) = shopping.proceed_to_checkout().err().unwrap();
+    assert_eq!(
+        err,
+        OutOfStock(vec![Unavailable {
+            sku: sku("lamp"),
+            requested: 3,
+            available: 2,
+        }])
+    );
+    assert_eq!(shopping.state_name(), "Shopping");
+    // Nothing was reserved, not even the plant.
+    assert_eq!(inventory.available(&sku("plant")), Some(1));
+
+    let shopping = match shopping.set_quantity(&sku("lamp"), 2) {
+        CartOutcome::StillShopping(shopping) => shopping,
+        CartOutcome::Emptied(_) => unreachable!(),
+    };
+    assert!(shopping.proceed_to_checkout().is_ok());
+    assert_eq!(inventory.available(&sku("plant")), Some(0));
+
+    let (_, err) = visit(&shop, "bob")
+        .add_item(plant())
+        .proceed_to_checkout()
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "not enough in stock: plant (1 wanted, 0 left)"
+    );
+}
+
+#[test]
+fn cancelling_releases_the_reservation() {
+    let (_, inventory, shop) = setup();
+    let checkout = visit(&shop, "alice")
+        .add_item(plant())
+        .proceed_to_checkout()
+        .ok()
+        .unwrap();
+    assert_eq!(inventory.available(&sku("plant")), Some(0));
+
+    let shopping = checkout.cancel_checkout();
+    assert_eq!(inventory.available(&sku("plant")), Some(1));
+
+    // Someone else gets there first.
+    let bob = visit(&shop, "bob")
+        .add_item(plant())
+        .proceed_to_checkout()
+        .ok()
+        .unwrap();
+    let (shopping, _) = shopping.proceed_to_checkout().err().unwrap();
+
+    shopping.clear_cart().leave();
+    bob.cancel_checkout().clear_cart().leave();
+    assert_eq!(inventory.available(&sku("plant")), Some(1));
+}
+
+#[test]
+fn every_visit_has_its_own_reservation() {
+    let (_, inventory, shop) = setup();
+    let checkout = visit(&shop, "alice")
+        .add_item(lamp())
+        .proceed_to_checkout()
+        .ok()
+        .unwrap();
+
+    // The same customer, checking out in another tab.
+    let other_tab = visit(&shop, "alice")
+        .add_item(lamp())
+        .proceed_to_checkout()
+        .ok()
+        .unwrap();
+    assert_eq!(inventory.available(&sku("lamp")), Some(0));
+
+    // Leaving a third tab without checking out doesn't touch either of them.
+    visit(&shop, "alice").leave();
+    assert_eq!(inventory.available(&sku("lamp")), Some(0));
+
+    other_tab.cancel_checkout();
+    assert_eq!(inventory.available(&sku("lamp")), Some(1));
+    ready_to_pay(checkout, "NL")
+        .finalise_payment(&MockGateway::new())
+        .ok()
+        .unwrap();
+    assert_eq!(inventory.on_hand(&sku("lamp")), Some(1));
+    assert_eq!(inventory.available(&sku("lamp")), Some(1));
+}
+
+#[test]
+fn reservations_expire() {
+    let (clock, inventory, shop) = setup();
+    let alice = visit(&shop, "alice")
+        .add_item(plant())
+        .proceed_to_checkout()
+        .ok()
+        .unwrap();
+
+    clock.advance(Duration::from_secs(599));
+    assert_eq!(inventory.available(&sku("plant")), Some(0));
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(inventory.available(&sku("plant")), Some(1));
+
+    // An expired reservation can still be paid for while there's stock...
+    let bob = visit(&shop, "bob").add_item(plant());
+    let carol = visit(&shop, "carol")
+        .add_item(plant())
+        .proceed_to_checkout()
+        .ok()
+        .unwrap();
+    carol.cancel_checkout();
+    ready_to_pay(alice, "NL")
+        .finalise_payment(&MockGateway::new())
+        .ok()
+        .unwrap();
+    assert_eq!(inventory.on_hand(&sku("plant")), Some(0));
+
+    // ...but not once it's gone.
+    inventory.set_stock("plant", 1);
+    let bob = bob.proceed_to_checkout().ok().unwrap();
+    clock.advance(Duration::from_secs(600));
+    let dave = visit(&shop, "dave")
+        .add_item(plant())
+        .proceed_to_checkout()
+        .ok()
+        .unwrap();
+    let (bob, err) = ready_to_pay(bob, "NL")
+        .finalise_payment(&MockGateway::new())
+        .err()
+        .unwrap();
+    assert!(matches!(
+        err,
+        PaymentError::OutOfStock(OutOfStock(items)) if items[0].available == 0
+    ));
+    assert_eq!(bob.state_name(), "ReadyToPay");
+    ready_to_pay(dave, "NL")
+        .finalise_payment(&MockGateway::new())
+        .ok()
+        .unwrap();
+}
+
+#[test]
+fn the_default_shop_tracks_no_stock() {
+    let shopping = Customer::visit_site().add_item(lamp());
+    let shopping = (0..100).fold(shopping, |shopping, _| shopping.add_item(lamp()));
+    assert!(shopping.proceed_to_checkout().is_ok());
+}
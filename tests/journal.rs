@@ -0,0 +1,152 @@
+use stated::online_shop::{
+    replay, replay_in, Action, CartOutcome, Customer, CustomerEvent, CustomerId, InvalidTransition,
+    Journal, MockGateway, Operation, PaymentMethod, Region, ReplayError, ShippingOption, Shop,
+    TaxTable,
+};
+
+mod common;
+use common::{lamp, tea};
+
+fn shop(gateway: &MockGateway) -> Shop {
+    Shop::new()
+        .with_taxes(TaxTable::demo())
+        .with_payments(gateway.clone())
+}
+
+// Everything alice did, from visiting the site to paying.
+fn record(shop: &Shop, gateway: &MockGateway) -> Vec<CustomerEvent> {
+    let journal = Journal::new();
+    let shopping = Customer::visit_shop(shop, CustomerId::new("alice"), journal.clone())
+        .add_item(tea())
+        .add_item(lamp())
+        .add_item(lamp());
+    let shopping = match shopping.pop_item() {
+        CartOutcome::StillShopping(shopping) => shopping,
+        CartOutcome::Emptied(_) => unreachable!(),
+    };
+    shopping
+        .proceed_to_checkout()
+        .ok()
+        .unwrap()
+        .ship_to("NL".into())
+        .ok()
+        .unwrap()
+        .choose_shipping(ShippingOption::Express)
+        .choose_payment(PaymentMethod::Card)
+        .finalise_payment(gateway)
+        .ok()
+        .unwrap();
+    journal.events()
+}
+
+fn event(action: Action, state: &str) -> CustomerEvent {
+    CustomerEvent {
+        action,
+        state: state.to_string(),
+    }
+}
+
+#[test]
+fn replaying_a_journal_ends_in_the_same_state() {
+    let gateway = MockGateway::new();
+    let shop = shop(&gateway);
+    let events = record(&shop, &gateway);
+    assert_eq!(events.first(), Some(&event(Action::VisitSite, "Browsing")));
+    // Everything up to paying.
+    let (paid, events) = events.split_last().unwrap();
+    assert_eq!(*paid, event(Action::FinalisePayment, "Paid"));
+
+    let replayed = Journal::new();
+    let customer = replay_in(&shop, CustomerId::new("alice"), events, replayed.clone())
+        .ok()
+        .unwrap();
+    let customer = customer.try_into_ready_to_pay().ok().unwrap();
+    assert_eq!(*customer.id(), CustomerId::new("alice"));
+    assert_eq!(customer.shopping_cart().len(), 2);
+    assert_eq!(customer.region(), Some(&Region::from("NL")));
+    // Replaying goes through the same transitions as the first time round.
+    assert_eq!(replayed.events(), events);
+}
+
+#[test]
+fn replaying_a_payment_doesnt_charge_again() {
+    let gateway = MockGateway::new();
+    let shop = shop(&gateway);
+    let events = record(&shop, &gateway);
+    let calls = gateway.calls();
+    assert_eq!(calls, [Operation::Authorize, Operation::Capture]);
+
+    let err = replay_in(&shop, CustomerId::new("alice"), &events, Journal::new())
+        .err()
+        .unwrap();
+    assert_eq!(
+        err,
+        ReplayError::AlreadyPaid {
+            index: events.len() - 1
+        }
+    );
+    assert_eq!(gateway.calls(), calls);
+}
+
+#[test]
+fn journals_with_an_illegal_step_are_rejected() {
+    let gateway = MockGateway::new();
+    let mut events = record(&shop(&gateway), &gateway);
+    // Taking something out of the cart at checkout, before paying.
+    let index = events.len() - 4;
+    events.insert(index, event(Action::PopItem, "Shopping"));
+
+    let err = replay_in(
+        &shop(&gateway),
+        CustomerId::new("alice"),
+        &events,
+        Journal::new(),
+    )
+    .err()
+    .unwrap();
+    assert_eq!(
+        err,
+        ReplayError::IllegalStep {
+            index,
+            source: InvalidTransition {
+                from: "NeedsAddress",
+                action: Action::PopItem,
+            },
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "illegal step at event {}: can't pop_item while NeedsAddress",
+            index
+        )
+    );
+}
+
+#[test]
+fn journals_that_lead_somewhere_else_have_diverged() {
+    let events = [
+        event(Action::VisitSite, "Browsing"),
+        event(Action::AddItem(tea()), "Shopping"),
+        event(Action::AddItem(tea()), "Shopping"),
+        // There's still one left, so the cart isn't empty yet.
+        event(Action::PopItem, "Browsing"),
+    ];
+    assert_eq!(
+        replay(&events).err(),
+        Some(ReplayError::Diverged {
+            index: 3,
+            expected: "Browsing".to_string(),
+            actual: "Shopping",
+        })
+    );
+}
+
+#[test]
+fn journals_start_with_a_visit() {
+    assert_eq!(replay(&[]).err(), Some(ReplayError::MissingVisit));
+    assert_eq!(
+        replay(&[event(Action::AddItem(tea()), "Shopping")]).err(),
+        Some(ReplayError::MissingVisit)
+    );
+}
@@ -0,0 +1,64 @@
+use stated::model::{Checker, Property, Violation};
+use stated::online_shop::{Action, Product, Region};
+
+mod common;
+use common::{eur, tea};
+
+// There's no tax rule for alcohol, so it can't be shipped anywhere.
+fn wine() -> Product {
+    Product::new("wine", "Red wine", eur(1250)).with_tax_category("alcohol")
+}
+
+#[test]
+fn the_shopping_flow_has_every_property() {
+    let report = Checker::new(6, vec![tea()]).check(&Property::ALL);
+    assert!(report.passed(), "{}", report);
+    assert_eq!(report.results.len(), Property::ALL.len());
+}
+
+#[test]
+fn violations_come_with_the_shortest_counterexample() {
+    let report = Checker::new(4, vec![wine()]).check(&[Property::NeverRefused]);
+    assert!(!report.passed());
+    assert_eq!(
+        report.results,
+        [(
+            Property::NeverRefused,
+            Some(Violation {
+                property: Property::NeverRefused,
+                message: "ship_to was refused in NeedsAddress with cart [1 x wine]: no tax rule \
+                          for alcohol products in NL"
+                    .to_string(),
+                trace: vec![
+                    Action::AddItem(wine()),
+                    Action::ProceedToCheckout,
+                    Action::ShipTo(Region::from("NL")),
+                ],
+            })
+        )]
+    );
+    assert!(report
+        .to_string()
+        .contains("counterexample: visit -> add wine -> checkout -> ship NL\n"));
+}
+
+#[test]
+fn refused_actions_lead_nowhere() {
+    let report = Checker::new(6, vec![wine()]).check(&Property::ALL);
+
+    let failed: Vec<_> = report
+        .results
+        .iter()
+        .filter_map(|(_, violation)| violation.as_ref())
+        .map(|violation| (violation.property, violation.message.as_str()))
+        .filter(|(property, _)| *property != Property::NeverRefused)
+        .collect();
+    // Still free to back out and leave, but never to pay.
+    assert_eq!(
+        failed,
+        [(
+            Property::AllStatesReachable,
+            "never reached NeedsShipping, NeedsPayment, ReadyToPay, Paid"
+        )]
+    );
+}
@@ -0,0 +1,111 @@
+use stated::online_shop::{
+    Customer, Eur, ExchangeRate, FixedRates, Jpy, Money, MoneyError, Product, Rounding, Usd,
+};
+
+mod common;
+use common::eur;
+
+#[test]
+fn totals_add_up_the_line_totals() {
+    let shopping = Customer::visit_site()
+        .add_item(Product::new("tea", "Loose leaf tea", eur(450)))
+        .add_item(Product::new("mug", "Enamel mug", eur(1200)))
+        .add_item(Product::new("tea", "Loose leaf tea", eur(450)));
+    assert_eq!(shopping.subtotal(), Ok(eur(2100)));
+
+    let checkout = shopping.proceed_to_checkout().ok().unwrap();
+    assert_eq!(checkout.total(), Ok(eur(2100)));
+}
+
+#[test]
+fn prices_in_other_currencies_are_converted_first() {
+    let rates = FixedRates::new().with_rate::<Usd, Eur>(ExchangeRate::new(92, 100));
+    let price: Money<Usd> = Money::from_minor(1300);
+    let price: Money<Eur> = price.convert(&rates, Rounding::HalfEven).unwrap();
+
+    let shopping = Customer::visit_site().add_item(Product::new("mug", "Enamel mug", price));
+    assert_eq!(shopping.total(), Ok(eur(1196)));
+}
+
+#[test]
+fn conversions_need_a_rate() {
+    let rates = FixedRates::new().with_rate::<Usd, Eur>(ExchangeRate::new(92, 100));
+    assert_eq!(
+        eur(100).convert::<Usd>(&rates, Rounding::HalfEven),
+        Err(MoneyError::NoRate {
+            from: "EUR",
+            to: "USD"
+        })
+    );
+    assert_eq!(
+        eur(100).convert::<Eur>(&rates, Rounding::HalfEven),
+        Ok(eur(100))
+    );
+}
+
+#[test]
+fn conversions_account_for_minor_units() {
+    // 1 EUR = 162.5 JPY, and yen have no minor units.
+    let rates = FixedRates::new()
+        .with_rate::<Eur, Jpy>(ExchangeRate::new(1625, 10))
+        .with_rate::<Jpy, Eur>(ExchangeRate::new(10, 1625));
+    let yen: Money<Jpy> = eur(1001).convert(&rates, Rounding::HalfUp).unwrap();
+    assert_eq!(yen.minor(), 1627);
+    assert_eq!(yen.convert(&rates, Rounding::HalfUp), Ok(eur(1001)));
+}
+
+#[test]
+fn arithmetic_is_checked() {
+    assert_eq!(eur(i64::MAX).checked_add(eur(1)), Err(MoneyError::Overflow));
+    assert_eq!(eur(i64::MAX).checked_mul(2), Err(MoneyError::Overflow));
+    assert_eq!(
+        eur(100).checked_mul_ratio(1, 0, Rounding::Down),
+        Err(MoneyError::DivisionByZero)
+    );
+}
+
+#[test]
+fn ratios_round_as_asked() {
+    let cases = [
+        (Rounding::Down, 250, -250, 249, -249),
+        (Rounding::Up, 251, -251, 250, -250),
+        (Rounding::HalfUp, 251, -251, 250, -250),
+        (Rounding::HalfEven, 250, -250, 250, -250),
+    ];
+    for (rounding, half, negative_half, under_half, negative_under_half) in cases {
+        // 50.1 / 2 and 49.9 / 2, and their negatives, in cents.
+        let halve = |cents| {
+            eur(cents)
+                .checked_mul_ratio(1, 2, rounding)
+                .unwrap()
+                .minor()
+        };
+        assert_eq!(halve(501), half, "{:?}", rounding);
+        assert_eq!(halve(-501), negative_half, "{:?}", rounding);
+        assert_eq!(halve(499), under_half, "{:?}", rounding);
+        assert_eq!(halve(-499), negative_under_half, "{:?}", rounding);
+    }
+    // 2.5 goes down to 2 when rounding half to even, but 3.5 goes up to 4.
+    assert_eq!(
+        eur(5).checked_mul_ratio(1, 2, Rounding::HalfEven),
+        Ok(eur(2))
+    );
+    assert_eq!(
+        eur(7).checked_mul_ratio(1, 2, Rounding::HalfEven),
+        Ok(eur(4))
+    );
+}
+
+#[test]
+fn display_uses_the_currency_decimals() {
+    assert_eq!(eur(1250).to_string(), "12.50 EUR");
+    assert_eq!(eur(-5).to_string(), "-0.05 EUR");
+    assert_eq!(Money::<Jpy>::from_minor(1200).to_string(), "1200 JPY");
+}
+
+#[test]
+fn deserializing_checks_the_currency() {
+    let json = serde_json::to_string(&eur(450)).unwrap();
+    assert_eq!(serde_json::from_str::<Money<Eur>>(&json).unwrap(), eur(450));
+    assert!(serde_json::from_str::<Money<Usd>>(&json).is_err());
+}
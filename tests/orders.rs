@@ -0,0 +1,241 @@
+use std::time::Duration;
+
+use stated::online_shop::{
+    Action, AnyCustomer, CartOutcome, Coupon, Customer, CustomerId, Discount, FixedClock,
+    InMemoryInventory, Inventory, MockGateway, MockResponse, Money, NoopSink, Operation, Order,
+    OrderId, Paid, PaymentError, PaymentMethod, PromotionEngine, Region, ShippingOption, Shop, Sku,
+    TaxTable, Timestamp,
+};
+
+mod common;
+use common::product;
+
+// 2024-03-01T09:30:00Z
+const OPENING: Timestamp = Timestamp::from_unix(1_709_285_400);
+
+fn shop(clock: &FixedClock) -> Shop {
+    let promotions = PromotionEngine::new(clock.clone()).with_coupon(Coupon::new(
+        "FIVEOFF",
+        Discount::FixedOff(Money::from_minor(500)),
+    ));
+    Shop::new()
+        .with_promotions(promotions)
+        .with_taxes(TaxTable::demo())
+        .with_clock(clock.clone())
+}
+
+fn pay(shop: &Shop, id: &str) -> Order<Paid> {
+    Customer::visit_shop(shop, CustomerId::new(id), NoopSink)
+        .add_item(product("mug"))
+        .add_item(product("mug"))
+        .add_item(product("tea"))
+        .proceed_to_checkout()
+        .ok()
+        .unwrap()
+        .ship_to("NL".into())
+        .ok()
+        .unwrap()
+        .apply_coupon("FIVEOFF")
+        .ok()
+        .unwrap()
+        .choose_shipping(ShippingOption::Express)
+        .choose_payment(PaymentMethod::Card)
+        .finalise_payment(&MockGateway::new())
+        .ok()
+        .unwrap()
+}
+
+#[test]
+fn paying_places_an_order_with_its_totals() {
+    let clock = FixedClock::new(OPENING);
+    let shop = shop(&clock);
+
+    let order = pay(&shop, "alice");
+    assert_eq!(*order.id(), OrderId::new("order-1"));
+    assert_eq!(*order.customer(), CustomerId::new("alice"));
+    assert_eq!(order.placed_at(), OPENING);
+    assert_eq!(order.lines().len(), 2);
+    assert_eq!(order.subtotal(), Money::from_minor(2850));
+    assert_eq!(order.discount(), Money::from_minor(500));
+    assert_eq!(order.tax().region, Region::from("NL"));
+    assert_eq!(order.tax().tax, Money::from_minor(374));
+    assert_eq!(order.total(), Money::from_minor(2350));
+    assert_eq!(order.shipping(), ShippingOption::Express);
+    assert_eq!(order.payment().amount, order.total());
+
+    clock.advance(Duration::from_secs(60));
+    let order = pay(&shop, "bob");
+    assert_eq!(*order.id(), OrderId::new("order-2"));
+    assert_eq!(order.placed_at(), Timestamp::from_unix(1_709_285_460));
+}
+
+#[test]
+fn orders_print_as_receipts() {
+    let order = pay(&shop(&FixedClock::new(OPENING)), "alice");
+    assert_eq!(
+        order.receipt().to_string(),
+        "\
+Order order-1 for alice, placed 2024-03-01T09:30:00Z
+    2 x mug     Enamel mug               24.00 EUR
+    1 x tea     Loose leaf tea            4.50 EUR
+  Subtotal                               28.50 EUR
+  Discount (FIVEOFF)                     -5.00 EUR
+  Tax (NL, included)                      3.74 EUR
+  Total                                  23.50 EUR
+Shipping express to NL
+Paid by card, reference pay-2"
+    );
+}
+
+#[test]
+fn orders_serialize_to_json() {
+    let order = pay(&shop(&FixedClock::new(OPENING)), "alice");
+    let json = serde_json::to_value(&order).unwrap();
+    assert_eq!(json["id"], "order-1");
+    assert_eq!(json["customer"], "alice");
+    assert_eq!(json["lines"].as_array().unwrap().len(), 2);
+    assert_eq!(json["placed_at"], 1_709_285_400);
+    assert_eq!(json["total"]["minor"], 2350);
+}
+
+#[test]
+fn leaving_with_an_emptied_cart_leaves_a_session_behind() {
+    let clock = FixedClock::new(OPENING);
+    let shop = shop(&clock);
+    let checkout = Customer::visit_shop(&shop, CustomerId::new("alice"), NoopSink)
+        .add_item(product("lamp"))
+        .add_item(product("tea"))
+        .proceed_to_checkout()
+        .ok()
+        .unwrap()
+        .apply_coupon("FIVEOFF")
+        .ok()
+        .unwrap();
+    clock.advance(Duration::from_secs(600));
+    let browsing = match checkout.cancel_checkout().pop_item() {
+        CartOutcome::StillShopping(shopping) => shopping.clear_cart(),
+        CartOutcome::Emptied(_) => panic!("the lamp should still be in the cart"),
+    };
+
+    let session = browsing.leave().unwrap();
+    assert_eq!(session.customer, CustomerId::new("alice"));
+    assert_eq!(session.started_at, OPENING);
+    assert_eq!(session.left_at, Timestamp::from_unix(1_709_286_000));
+    assert_eq!(session.last_cart.len(), 1);
+    assert_eq!(session.last_cart[0].product.sku, Sku::from("lamp"));
+    assert_eq!(session.value(), Ok(Money::from_minor(3999)));
+    assert!(session.reached_checkout);
+    assert_eq!(session.coupon.as_deref(), Some("FIVEOFF"));
+
+    // Nothing's abandoned if nothing was ever put in the cart.
+    let customer = Customer::visit_shop(&shop, CustomerId::new("bob"), NoopSink);
+    assert_eq!(customer.leave(), None);
+}
+
+#[test]
+fn runtime_customers_keep_what_they_leave_behind() {
+    let shop = shop(&FixedClock::new(OPENING));
+    let customer: AnyCustomer = Customer::visit_shop(&shop, CustomerId::new("alice"), NoopSink)
+        .add_item(product("mug"))
+        .into();
+
+    let customer = customer.apply(Action::ClearCart).ok().unwrap();
+    match customer.apply(Action::Leave).ok().unwrap() {
+        AnyCustomer::Left(Some(session)) => assert!(!session.reached_checkout),
+        other => panic!("expected an abandoned session, got {}", other.state_name()),
+    }
+
+    let customer: AnyCustomer = Customer::visit_shop(&shop, CustomerId::new("bob"), NoopSink)
+        .add_item(product("mug"))
+        .into();
+    let customer = [
+        Action::ProceedToCheckout,
+        Action::ShipTo("NL".into()),
+        Action::ChooseShipping(ShippingOption::Standard),
+        Action::ChoosePayment(PaymentMethod::Card),
+    ]
+    .into_iter()
+    .fold(customer, |customer, action| {
+        customer.apply(action).ok().unwrap()
+    });
+    assert_eq!(customer.state_name(), "ReadyToPay");
+    match customer.apply(Action::FinalisePayment).ok().unwrap() {
+        AnyCustomer::Paid(order) => assert_eq!(order.total(), Money::from_minor(1200)),
+        other => panic!("expected an order, got {}", other.state_name()),
+    }
+}
+
+#[test]
+fn orders_are_packed_shipped_and_delivered() {
+    let order = pay(&shop(&FixedClock::new(OPENING)), "alice");
+    assert_eq!(order.state_name(), "Paid");
+
+    let packed = order.pack();
+    assert_eq!(packed.state_name(), "Packed");
+    let shipped = packed.ship("TRACK-123");
+    assert_eq!(shipped.state().tracking, "TRACK-123");
+    let delivered = shipped.deliver(Timestamp::from_unix(1_709_500_000));
+    assert_eq!(delivered.state_name(), "Delivered");
+    assert_eq!(
+        delivered.state().delivered_at,
+        Timestamp::from_unix(1_709_500_000)
+    );
+    // Nothing about what was bought changes along the way.
+    assert_eq!(*delivered.id(), OrderId::new("order-1"));
+    assert_eq!(delivered.total(), Money::from_minor(2350));
+}
+
+#[test]
+fn cancelling_refunds_the_order_in_full() {
+    let gateway = MockGateway::new();
+    let inventory = InMemoryInventory::new(FixedClock::new(OPENING)).with_stock("mug", 3);
+    let clock = FixedClock::new(OPENING);
+    let shop = shop(&clock)
+        .with_payments(gateway.clone())
+        .with_inventory(inventory.clone());
+    let customer = Customer::visit_shop(&shop, CustomerId::new("alice"), NoopSink)
+        .add_item(product("mug"))
+        .proceed_to_checkout()
+        .ok()
+        .unwrap()
+        .ship_to("DE".into())
+        .ok()
+        .unwrap()
+        .choose_shipping(ShippingOption::Standard)
+        .choose_payment(PaymentMethod::Card);
+    let order = customer.finalise_payment(&gateway).ok().unwrap();
+    assert_eq!(inventory.available(&Sku::new("mug")), Some(2));
+
+    let cancelled = order.pack().cancel(&gateway, &inventory).ok().unwrap();
+    let refund = cancelled.state().refund.as_ref().unwrap();
+    assert_eq!(refund.amount, Money::from_minor(1200));
+    assert_eq!(refund.payment, cancelled.payment().reference);
+    assert_eq!(
+        gateway.refunded(&cancelled.payment().reference),
+        Some(Money::from_minor(1200))
+    );
+    // The mug goes back on the shelf.
+    assert_eq!(inventory.available(&Sku::new("mug")), Some(3));
+}
+
+#[test]
+fn a_failed_refund_hands_the_order_back() {
+    let gateway = MockGateway::new();
+    let inventory = InMemoryInventory::new(FixedClock::new(OPENING)).with_stock("mug", 5);
+    let shop = shop(&FixedClock::new(OPENING)).with_inventory(inventory.clone());
+    let order = pay(&shop, "alice");
+    gateway.script(Operation::Refund, MockResponse::TimeOut);
+
+    // The order was paid through another gateway, which this one has never
+    // heard of.
+    let (order, err) = order.cancel(&gateway, &inventory).err().unwrap();
+    assert_eq!(err, PaymentError::TimedOut);
+    assert_eq!(order.state_name(), "Paid");
+    let (order, err) = order.cancel(&gateway, &inventory).err().unwrap();
+    assert_eq!(
+        err,
+        PaymentError::Unknown(order.payment().reference.clone())
+    );
+    // Nothing is restocked until the money's been given back.
+    assert_eq!(inventory.available(&Sku::new("mug")), Some(3));
+}
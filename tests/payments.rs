@@ -0,0 +1,233 @@
+use stated::online_shop::{
+    Action, AnyCustomer, ApplyError, Catalogue, Coupon, CustomerId, Discount, InMemoryInventory,
+    Inventory, LineItem, MockGateway, MockResponse, MoneyError, Operation, OutOfStock,
+    PaymentError, PaymentGateway, PaymentMethod, PromotionEngine, Rejection, ReservationId, Shop,
+    Sku, SystemClock, TaxError, TaxTable, Unavailable,
+};
+
+mod common;
+use common::{checkout, eur, lamp, ready_to_pay};
+
+fn shop() -> Shop {
+    let promotions = PromotionEngine::new(SystemClock)
+        .with_coupon(Coupon::new("ONCE", Discount::FixedOff(eur(1000))).with_uses_per_customer(1));
+    Shop::new()
+        .with_promotions(promotions)
+        .with_taxes(TaxTable::demo())
+        .with_inventory(InMemoryInventory::new(SystemClock).with_stock("lamp", 1))
+}
+
+#[test]
+fn a_declined_payment_can_be_retried() {
+    let shop = shop();
+    let gateway = MockGateway::new()
+        .with_script(
+            Operation::Authorize,
+            MockResponse::Decline("insufficient funds".into()),
+        )
+        .with_script(Operation::Authorize, MockResponse::TryAgain);
+    let customer = checkout(&shop, "alice", &[lamp()])
+        .apply_coupon("ONCE")
+        .ok()
+        .unwrap();
+    let customer = ready_to_pay(customer, "NL");
+
+    let (customer, err) = customer.finalise_payment(&gateway).err().unwrap();
+    assert_eq!(
+        err,
+        PaymentError::Declined("insufficient funds".to_string())
+    );
+    assert_eq!(customer.state_name(), "ReadyToPay");
+    let (customer, err) = customer.finalise_payment(&gateway).err().unwrap();
+    assert_eq!(err, PaymentError::TryAgain);
+    // Nothing is taken out of stock or counted towards the coupon's limit.
+    assert_eq!(shop.inventory.available(&"lamp".into()), Some(0));
+    assert_eq!(shop.promotions.uses(&CustomerId::new("alice"), "ONCE"), 0);
+
+    let customer = customer.choose_payment(PaymentMethod::PayPal);
+    let order = customer.finalise_payment(&gateway).ok().unwrap();
+    assert_eq!(*order.customer(), CustomerId::new("alice"));
+    assert_eq!(order.coupon(), Some("ONCE"));
+    assert_eq!(order.total(), eur(2999));
+    assert_eq!(order.payment().reference, "pay-2");
+    assert_eq!(order.payment().amount, eur(2999));
+    assert_eq!(order.payment().method, PaymentMethod::PayPal);
+    assert_eq!(shop.inventory.available(&"lamp".into()), Some(0));
+    assert_eq!(shop.promotions.uses(&CustomerId::new("alice"), "ONCE"), 1);
+}
+
+#[test]
+fn a_failed_capture_voids_the_authorization() {
+    let shop = shop();
+    let gateway = MockGateway::new().with_script(Operation::Capture, MockResponse::TimeOut);
+
+    let (customer, err) = ready_to_pay(checkout(&shop, "alice", &[lamp()]), "NL")
+        .finalise_payment(&gateway)
+        .err()
+        .unwrap();
+    assert_eq!(err, PaymentError::TimedOut);
+    assert_eq!(
+        gateway.calls(),
+        [Operation::Authorize, Operation::Capture, Operation::Void]
+    );
+
+    customer.cancel_checkout();
+    assert_eq!(shop.inventory.available(&"lamp".into()), Some(1));
+}
+
+// Sells out between reserving and taking the stock, as if the last one had
+// been sold somewhere else in the meantime.
+struct SellsOut;
+
+impl Inventory for SellsOut {
+    fn available(&self, _: &Sku) -> Option<u32> {
+        None
+    }
+
+    fn reserve(&self, _: &ReservationId, _: &[LineItem]) -> Result<(), OutOfStock> {
+        Ok(())
+    }
+
+    fn release(&self, _: &ReservationId) {}
+
+    fn restock(&self, _: &[LineItem]) {}
+
+    fn commit(&self, _: &ReservationId, cart: &[LineItem]) -> Result<(), OutOfStock> {
+        let unavailable = cart
+            .iter()
+            .map(|line| Unavailable {
+                sku: line.product.sku.clone(),
+                requested: line.quantity.get(),
+                available: 0,
+            })
+            .collect();
+        Err(OutOfStock(unavailable))
+    }
+}
+
+#[test]
+fn money_taken_for_stock_that_ran_out_is_given_back() {
+    let shop = Shop::new()
+        .with_taxes(TaxTable::demo())
+        .with_inventory(SellsOut);
+    let gateway = MockGateway::new();
+
+    let customer = ready_to_pay(checkout(&shop, "alice", &[lamp()]), "NL");
+    let (customer, err) = customer.finalise_payment(&gateway).err().unwrap();
+    assert!(matches!(err, PaymentError::OutOfStock(_)));
+    assert_eq!(gateway.refunded("pay-2"), Some(eur(3999)));
+
+    // If that fails too, whoever handles the error is told what was taken.
+    gateway.script(Operation::Refund, MockResponse::TimeOut);
+    let (customer, err) = customer.finalise_payment(&gateway).err().unwrap();
+    let PaymentError::NotRefunded {
+        payment,
+        out_of_stock,
+        refund,
+    } = err
+    else {
+        panic!("expected the refund to fail, got {:?}", err);
+    };
+    assert_eq!(payment.reference, "pay-5");
+    assert_eq!(payment.amount, eur(3999));
+    assert_eq!(out_of_stock.0[0].sku, Sku::from("lamp"));
+    assert_eq!(*refund, PaymentError::TimedOut);
+    assert_eq!(gateway.refunded("pay-5"), Some(eur(0)));
+    assert_eq!(customer.state_name(), "ReadyToPay");
+}
+
+#[test]
+fn nothing_is_given_back_for_stock_that_ran_out_if_nothing_was_taken() {
+    let promotions = PromotionEngine::new(SystemClock)
+        .with_coupon(Coupon::new("FREE", Discount::PercentOff(100)));
+    let shop = Shop::new()
+        .with_promotions(promotions)
+        .with_taxes(TaxTable::demo())
+        .with_inventory(SellsOut);
+    let gateway = MockGateway::new();
+
+    let customer = ready_to_pay(checkout(&shop, "alice", &[lamp()]), "NL");
+    let customer = customer.apply_coupon("FREE").ok().unwrap();
+    let (customer, err) = customer.finalise_payment(&gateway).err().unwrap();
+    assert!(matches!(err, PaymentError::OutOfStock(_)));
+    assert_eq!(gateway.calls(), [Operation::Authorize, Operation::Capture]);
+    assert_eq!(customer.state_name(), "ReadyToPay");
+}
+
+#[test]
+fn money_errors_are_not_tax_errors() {
+    assert_eq!(
+        PaymentError::from(MoneyError::Overflow),
+        PaymentError::Money(MoneyError::Overflow)
+    );
+    assert_eq!(
+        PaymentError::from(TaxError::Money(MoneyError::Overflow)),
+        PaymentError::Tax(TaxError::Money(MoneyError::Overflow))
+    );
+}
+
+#[test]
+fn the_amount_due_is_charged_with_the_tax() {
+    let shop = shop();
+    let gateway = MockGateway::new();
+    let customer = ready_to_pay(checkout(&shop, "alice", &[lamp()]), "US-NY");
+    let customer = customer.apply_coupon("ONCE").ok().unwrap();
+
+    // 39.99 - 10.00, plus 2.66 tax
+    let order = customer.finalise_payment(&gateway).ok().unwrap();
+    assert_eq!(order.payment().amount, eur(3265));
+}
+
+#[test]
+fn payments_can_be_refunded_in_parts() {
+    let gateway = MockGateway::new();
+    let authorization = gateway.authorize(eur(1000), PaymentMethod::Card).unwrap();
+    let payment = gateway.capture(&authorization).unwrap();
+    assert_eq!(
+        gateway.capture(&authorization),
+        Err(PaymentError::Unknown("auth-1".to_string()))
+    );
+
+    let refund = gateway.refund(&payment, eur(400)).unwrap();
+    assert_eq!(refund.payment, payment.reference);
+    assert_eq!(
+        gateway.refund(&payment, eur(601)),
+        Err(PaymentError::RefundTooLarge {
+            reference: payment.reference.clone(),
+            requested: eur(601),
+            refundable: eur(600),
+        })
+    );
+    assert_eq!(
+        gateway.refund(&payment, eur(0)),
+        Err(PaymentError::InvalidAmount(eur(0)))
+    );
+    gateway.refund(&payment, eur(600)).unwrap();
+    assert_eq!(gateway.refunded(&payment.reference), Some(eur(1000)));
+}
+
+#[test]
+fn runtime_customers_pay_through_the_shop_gateway() {
+    let gateway = MockGateway::new();
+    let shop = shop().with_payments(gateway.clone());
+    gateway.script(Operation::Authorize, MockResponse::TimeOut);
+
+    let pay = Action::parse("pay", &Catalogue::demo()).unwrap();
+    assert_eq!(pay, Action::FinalisePayment);
+    assert_eq!(
+        Action::parse("payment paypal", &Catalogue::demo()),
+        Ok(Action::ChoosePayment(PaymentMethod::PayPal))
+    );
+    assert!(Action::parse("payment cash", &Catalogue::demo()).is_err());
+
+    let customer = AnyCustomer::from(ready_to_pay(checkout(&shop, "alice", &[lamp()]), "NL"));
+    let customer = match customer.apply(pay.clone()) {
+        Err(ApplyError::Rejected {
+            customer,
+            reason: Rejection::Payment(PaymentError::TimedOut),
+        }) => customer,
+        other => panic!("expected the payment to time out, got {:?}", other.err()),
+    };
+    assert_eq!(customer.state_name(), "ReadyToPay");
+    assert!(matches!(customer.apply(pay), Ok(AnyCustomer::Paid(_))));
+}
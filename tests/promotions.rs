@@ -0,0 +1,253 @@
+use std::time::Duration;
+
+use stated::online_shop::{
+    CartOutcome, Coupon, CouponError, CustomerId, Discount, FixedClock, MockGateway, PaymentError,
+    PromotionEngine, Shop, TaxTable, Timestamp,
+};
+
+mod common;
+use common::{checkout, eur, lamp, ready_to_pay, tea};
+
+// 2024-01-01T00:00:00Z
+const NEW_YEAR: Timestamp = Timestamp::from_unix(1_704_067_200);
+
+fn shop(clock: &FixedClock) -> Shop {
+    let promotions = PromotionEngine::new(clock.clone())
+        .with_coupon(Coupon::new("TENPERCENT", Discount::PercentOff(10)))
+        .with_coupon(
+            Coupon::new("FIVEOFF", Discount::FixedOff(eur(500))).with_minimum_spend(eur(3000)),
+        )
+        .with_coupon(Coupon::new("HUGE", Discount::FixedOff(eur(100_000))))
+        .with_coupon(Coupon::new(
+            "TEATIME",
+            Discount::BuyXGetY {
+                sku: "tea".into(),
+                buy: 2,
+                get: 1,
+            },
+        ))
+        .with_coupon(Coupon::new(
+            "GREEDY",
+            Discount::BuyXGetY {
+                sku: "tea".into(),
+                buy: u32::MAX,
+                get: 1,
+            },
+        ))
+        .with_coupon(Coupon::new("ONCE", Discount::PercentOff(50)).with_uses_per_customer(1))
+        .with_coupon(
+            Coupon::new("JANUARY", Discount::PercentOff(20))
+                .with_expiry(NEW_YEAR + Duration::from_secs(31 * 86_400)),
+        );
+    Shop::new()
+        .with_promotions(promotions)
+        .with_taxes(TaxTable::demo())
+}
+
+fn rejection<T>(result: Result<T, (T, CouponError)>) -> CouponError {
+    match result {
+        Ok(_) => panic!("the coupon was accepted"),
+        Err((_, err)) => err,
+    }
+}
+
+#[test]
+fn discounts_come_off_the_total() {
+    let clock = FixedClock::new(NEW_YEAR);
+    let shop = shop(&clock);
+
+    let customer = checkout(&shop, "alice", &[tea(), lamp()]);
+    let customer = customer.apply_coupon("TENPERCENT").ok().unwrap();
+    assert_eq!(customer.subtotal(), Ok(eur(4449)));
+    assert_eq!(customer.discount(), Ok(eur(444)));
+    assert_eq!(customer.total(), Ok(eur(4005)));
+
+    let customer = customer.apply_coupon("FIVEOFF").ok().unwrap();
+    assert_eq!(customer.coupon(), Some("FIVEOFF"));
+    assert_eq!(customer.total(), Ok(eur(3949)));
+
+    // Never less than nothing.
+    let customer = customer.apply_coupon("HUGE").ok().unwrap();
+    assert_eq!(customer.total(), Ok(eur(0)));
+
+    let customer = customer.remove_coupon();
+    assert_eq!(customer.coupon(), None);
+    assert_eq!(customer.total(), Ok(eur(4449)));
+}
+
+#[test]
+fn buy_x_get_y_makes_every_third_free() {
+    let clock = FixedClock::new(NEW_YEAR);
+    let shop = shop(&clock);
+
+    let customer = checkout(&shop, "alice", &[tea(), tea()]);
+    let (customer, err) = customer.apply_coupon("TEATIME").err().unwrap();
+    assert_eq!(err, CouponError::NothingToDiscount("TEATIME".to_string()));
+
+    let shopping = customer.cancel_checkout();
+    let shopping = (1..=5).fold(shopping, |shopping, _| shopping.add_item(tea()));
+    let customer = shopping
+        .proceed_to_checkout()
+        .ok()
+        .unwrap()
+        .apply_coupon("TEATIME")
+        .ok()
+        .unwrap();
+    // 7 teas, 2 of them free.
+    assert_eq!(customer.total(), Ok(eur(5 * 450)));
+}
+
+#[test]
+fn deals_too_big_for_any_cart_never_apply() {
+    let clock = FixedClock::new(NEW_YEAR);
+    let shop = shop(&clock);
+    let customer = checkout(&shop, "alice", &[tea(), tea(), tea()]);
+
+    assert_eq!(
+        rejection(customer.apply_coupon("GREEDY")),
+        CouponError::NothingToDiscount("GREEDY".to_string())
+    );
+}
+
+#[test]
+fn invalid_codes_hand_the_customer_back() {
+    let clock = FixedClock::new(NEW_YEAR);
+    let shop = shop(&clock);
+    let customer = checkout(&shop, "alice", &[tea()]);
+
+    let (customer, err) = customer.apply_coupon("NOPE").err().unwrap();
+    assert_eq!(err, CouponError::UnknownCode("NOPE".to_string()));
+    assert_eq!(customer.coupon(), None);
+
+    assert_eq!(
+        rejection(customer.apply_coupon("FIVEOFF")),
+        CouponError::MinimumSpendNotMet {
+            code: "FIVEOFF".to_string(),
+            minimum_spend: eur(3000),
+            subtotal: eur(450),
+        }
+    );
+}
+
+#[test]
+fn coupons_expire() {
+    let clock = FixedClock::new(NEW_YEAR);
+    let shop = shop(&clock);
+
+    let customer = checkout(&shop, "alice", &[tea()]);
+    let customer = customer.apply_coupon("JANUARY").ok().unwrap();
+    assert_eq!(customer.total(), Ok(eur(360)));
+
+    // Expiring while applied takes the discount away, and it can't be applied
+    // again.
+    clock.advance(Duration::from_secs(31 * 86_400));
+    assert_eq!(customer.total(), Ok(eur(450)));
+    assert!(matches!(
+        rejection(customer.apply_coupon("JANUARY")),
+        CouponError::Expired { .. }
+    ));
+}
+
+#[test]
+fn usage_is_limited_per_customer() {
+    let clock = FixedClock::new(NEW_YEAR);
+    let shop = shop(&clock);
+
+    let customer = checkout(&shop, "alice", &[tea()]);
+    let customer = customer.apply_coupon("ONCE").ok().unwrap();
+    ready_to_pay(customer, "NL")
+        .finalise_payment(&MockGateway::new())
+        .ok()
+        .unwrap();
+    assert_eq!(shop.promotions.uses(&CustomerId::new("alice"), "ONCE"), 1);
+
+    let customer = checkout(&shop, "alice", &[tea()]);
+    assert_eq!(
+        rejection(customer.apply_coupon("ONCE")),
+        CouponError::UsageLimitReached {
+            code: "ONCE".to_string(),
+            uses_per_customer: 1,
+        }
+    );
+
+    let customer = checkout(&shop, "bob", &[tea()]);
+    assert!(customer.apply_coupon("ONCE").is_ok());
+}
+
+#[test]
+fn coupons_that_stop_applying_before_paying_are_not_paid_with() {
+    let clock = FixedClock::new(NEW_YEAR);
+    let shop = shop(&clock);
+    let gateway = MockGateway::new();
+
+    let customer = checkout(&shop, "alice", &[tea()]);
+    let customer = ready_to_pay(customer.apply_coupon("JANUARY").ok().unwrap(), "NL");
+    clock.advance(Duration::from_secs(31 * 86_400));
+    let (customer, err) = customer.finalise_payment(&gateway).err().unwrap();
+    assert!(matches!(
+        err,
+        PaymentError::Coupon(CouponError::Expired { .. })
+    ));
+    // Nothing was charged, and the customer is told before paying full price.
+    assert!(gateway.calls().is_empty());
+    assert_eq!(customer.coupon(), Some("JANUARY"));
+    let order = customer
+        .remove_coupon()
+        .finalise_payment(&gateway)
+        .ok()
+        .unwrap();
+    assert_eq!(order.coupon(), None);
+    assert_eq!(order.total(), eur(450));
+
+    // Used up in another session in the meantime.
+    let first = checkout(&shop, "bob", &[tea()])
+        .apply_coupon("ONCE")
+        .ok()
+        .unwrap();
+    let second = checkout(&shop, "bob", &[tea()])
+        .apply_coupon("ONCE")
+        .ok()
+        .unwrap();
+    ready_to_pay(first, "NL")
+        .finalise_payment(&gateway)
+        .ok()
+        .unwrap();
+    let (_, err) = ready_to_pay(second, "NL")
+        .finalise_payment(&gateway)
+        .err()
+        .unwrap();
+    assert_eq!(
+        err,
+        PaymentError::Coupon(CouponError::UsageLimitReached {
+            code: "ONCE".to_string(),
+            uses_per_customer: 1,
+        })
+    );
+    assert_eq!(shop.promotions.uses(&CustomerId::new("bob"), "ONCE"), 1);
+}
+
+#[test]
+fn total_follows_the_cart_after_cancelling_checkout() {
+    let clock = FixedClock::new(NEW_YEAR);
+    let shop = shop(&clock);
+
+    let customer = checkout(&shop, "alice", &[lamp()]);
+    let customer = customer.apply_coupon("FIVEOFF").ok().unwrap();
+    assert_eq!(customer.total(), Ok(eur(3499)));
+
+    // Dropping below the minimum spend takes the discount away, and going back
+    // above it brings it back.
+    let shopping = customer.cancel_checkout().add_item(tea());
+    assert_eq!(shopping.total(), Ok(eur(3949)));
+    let shopping = match shopping.remove_item(&"lamp".into()) {
+        CartOutcome::StillShopping(shopping) => shopping,
+        CartOutcome::Emptied(_) => unreachable!(),
+    };
+    assert_eq!(shopping.coupon(), Some("FIVEOFF"));
+    assert_eq!(shopping.total(), Ok(eur(450)));
+    let shopping = shopping.add_item(lamp());
+    assert_eq!(
+        shopping.proceed_to_checkout().ok().unwrap().total(),
+        Ok(eur(3949))
+    );
+}
@@ -0,0 +1,212 @@
+use std::num::NonZeroU32;
+use std::time::Duration;
+
+use stated::online_shop::{
+    Clock, Coupon, Delivered, Discount, FixedClock, MockGateway, MockResponse, Operation, Order,
+    PaymentError, PromotionEngine, RefundLine, ReturnError, ReturnLine, ReturnPolicy, Shop, Sku,
+    TaxTable, Timestamp,
+};
+
+mod common;
+use common::{checkout, eur, product, ready_to_pay};
+
+// 2024-03-01T09:30:00Z
+const DELIVERED_AT: Timestamp = Timestamp::from_unix(1_709_285_400);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+fn line(sku: &str, quantity: u32) -> ReturnLine {
+    ReturnLine::new(sku, NonZeroU32::new(quantity).unwrap())
+}
+
+fn refund(sku: &str, cents: i64) -> RefundLine {
+    RefundLine {
+        sku: Sku::from(sku),
+        amount: eur(cents),
+    }
+}
+
+// 2 mugs and a tea shipped to New York, where tax is added on top. The 5.00 off
+// comes off before tax, 4.21 of it off the mugs and 0.79 off the tea, so that's
+// 19.79 + 1.76 tax for the mugs and 3.71 for the tea, 25.26 paid.
+fn delivered(gateway: &MockGateway) -> Order<Delivered> {
+    let promotions = PromotionEngine::default()
+        .with_coupon(Coupon::new("FIVEOFF", Discount::FixedOff(eur(500))));
+    let shop = Shop::new()
+        .with_promotions(promotions)
+        .with_taxes(TaxTable::demo());
+
+    let customer = checkout(
+        &shop,
+        "alice",
+        &[product("mug"), product("mug"), product("tea")],
+    );
+    let customer = customer.apply_coupon("FIVEOFF").ok().unwrap();
+    let order = ready_to_pay(customer, "US-NY")
+        .finalise_payment(gateway)
+        .ok()
+        .unwrap();
+    assert_eq!(order.total(), eur(2526));
+    order.pack().ship("TRACK-123").deliver(DELIVERED_AT)
+}
+
+#[test]
+fn returned_lines_are_refunded_their_share_of_what_was_paid() {
+    let gateway = MockGateway::new();
+    let clock = FixedClock::new(DELIVERED_AT);
+    let policy = ReturnPolicy::new(clock.clone());
+    clock.advance(10 * DAY);
+
+    let requested = delivered(&gateway)
+        .request_return(&policy, vec![line("mug", 1), line("tea", 1)], "too small")
+        .ok()
+        .unwrap();
+    assert_eq!(requested.state().request.reason, "too small");
+    assert_eq!(requested.state().request.requested_at, clock.now());
+
+    let received = requested.receive_return();
+    // The mugs cost 21.55 and the tea 3.71 of the 25.26 paid, and only one of
+    // the two mugs comes back.
+    let due = received.refund_due().unwrap();
+    assert_eq!(due, [refund("mug", 1077), refund("tea", 371)]);
+
+    let refunded = received.refund(&gateway, due).ok().unwrap();
+    let reference = &refunded.payment().reference;
+    assert_eq!(refunded.state().refund.as_ref().unwrap().amount, eur(1448));
+    assert_eq!(gateway.refunded(reference), Some(eur(1448)));
+    assert_eq!(gateway.calls().last(), Some(&Operation::Refund));
+}
+
+#[test]
+fn returns_are_only_accepted_within_the_window() {
+    let gateway = MockGateway::new();
+    let clock = FixedClock::new(DELIVERED_AT + 30 * DAY);
+    let policy = ReturnPolicy::new(clock.clone());
+
+    let order = delivered(&gateway);
+    let requested = order.request_return(&policy, vec![line("tea", 1)], "changed my mind");
+    assert!(requested.is_ok());
+
+    clock.advance(Duration::from_secs(1));
+    let (order, err) = delivered(&gateway)
+        .request_return(&policy, vec![line("tea", 1)], "changed my mind")
+        .err()
+        .unwrap();
+    assert_eq!(
+        err,
+        ReturnError::WindowClosed {
+            delivered_at: DELIVERED_AT,
+            deadline: DELIVERED_AT + 30 * DAY,
+        }
+    );
+    assert_eq!(order.state_name(), "Delivered");
+
+    let policy = ReturnPolicy::new(clock).with_window(60 * DAY);
+    assert!(order
+        .request_return(&policy, vec![line("tea", 1)], "changed my mind")
+        .is_ok());
+}
+
+#[test]
+fn only_what_was_ordered_can_be_returned() {
+    let gateway = MockGateway::new();
+    let policy = ReturnPolicy::new(FixedClock::new(DELIVERED_AT));
+    let order = delivered(&gateway);
+
+    let (order, err) = order.request_return(&policy, vec![], "").err().unwrap();
+    assert_eq!(err, ReturnError::NothingToReturn);
+    let (order, err) = order
+        .request_return(&policy, vec![line("lamp", 1)], "")
+        .err()
+        .unwrap();
+    assert_eq!(err, ReturnError::NotInOrder(Sku::from("lamp")));
+    // Lines of the same product are added up.
+    let (order, err) = order
+        .request_return(&policy, vec![line("mug", 1), line("mug", 2)], "")
+        .err()
+        .unwrap();
+    assert_eq!(
+        err,
+        ReturnError::TooMany {
+            sku: Sku::from("mug"),
+            requested: 3,
+            ordered: 2,
+        }
+    );
+
+    let requested = order
+        .request_return(&policy, vec![line("mug", 1), line("mug", 1)], "")
+        .ok()
+        .unwrap();
+    assert_eq!(requested.state().request.lines, [line("mug", 2)]);
+}
+
+#[test]
+fn refunds_can_be_partial_but_never_more_than_was_paid() {
+    let gateway = MockGateway::new();
+    let policy = ReturnPolicy::new(FixedClock::new(DELIVERED_AT));
+    let received = delivered(&gateway)
+        .request_return(&policy, vec![line("mug", 1)], "chipped")
+        .ok()
+        .unwrap()
+        .receive_return();
+
+    let (received, err) = received
+        .refund(&gateway, vec![refund("mug", 1078)])
+        .err()
+        .unwrap();
+    assert_eq!(
+        err,
+        ReturnError::RefundTooLarge {
+            sku: Sku::from("mug"),
+            requested: eur(1078),
+            refundable: eur(1077),
+        }
+    );
+    let (received, err) = received
+        .refund(&gateway, vec![refund("mug", 600), refund("mug", 600)])
+        .err()
+        .unwrap();
+    assert!(matches!(err, ReturnError::RefundTooLarge { .. }));
+    let (received, err) = received
+        .refund(&gateway, vec![refund("tea", 100)])
+        .err()
+        .unwrap();
+    assert_eq!(err, ReturnError::NotReturned(Sku::from("tea")));
+
+    gateway.script(Operation::Refund, MockResponse::TryAgain);
+    let (received, err) = received
+        .refund(&gateway, vec![refund("mug", 500)])
+        .err()
+        .unwrap();
+    assert_eq!(err, ReturnError::Payment(PaymentError::TryAgain));
+    assert_eq!(received.state_name(), "ReturnReceived");
+
+    let refunded = received
+        .refund(&gateway, vec![refund("mug", 500)])
+        .ok()
+        .unwrap();
+    assert_eq!(refunded.state().lines, [refund("mug", 500)]);
+    assert_eq!(
+        gateway.refunded(&refunded.payment().reference),
+        Some(eur(500))
+    );
+}
+
+#[test]
+fn rejected_returns_refund_nothing() {
+    let gateway = MockGateway::new();
+    let policy = ReturnPolicy::new(FixedClock::new(DELIVERED_AT));
+    let rejected = delivered(&gateway)
+        .request_return(&policy, vec![line("tea", 1)], "stale")
+        .ok()
+        .unwrap()
+        .receive_return()
+        .reject_return("opened");
+
+    assert_eq!(rejected.state().reason, "opened");
+    assert_eq!(rejected.state().request.reason, "stale");
+    assert_eq!(
+        gateway.refunded(&rejected.payment().reference),
+        Some(eur(0))
+    );
+}
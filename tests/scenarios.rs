@@ -0,0 +1,82 @@
+use std::fs;
+use std::process::{Command, Output};
+
+use serde_json::{json, Value};
+use stated::online_shop::{Catalogue, Shop};
+use stated::scenario::{Failure, FailureKind, Scenario};
+
+#[test]
+fn checked_in_scenarios_pass() {
+    for entry in fs::read_dir("scenarios").unwrap() {
+        let path = entry.unwrap().path();
+        let source = fs::read_to_string(&path).unwrap();
+        let report = Scenario::parse(&source, &Catalogue::demo())
+            .unwrap()
+            .run(&Shop::demo());
+        assert!(report.passed, "{}: {}", path.display(), report);
+    }
+}
+
+const FAILING: &str = "tests/fixtures/pop_after_paying.txt";
+
+fn stated(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_stated"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn failing_scenarios_report_the_step_they_failed_at() {
+    let source = fs::read_to_string(FAILING).unwrap();
+    let report = Scenario::parse(&source, &Catalogue::demo())
+        .unwrap()
+        .run(&Shop::demo());
+    assert!(!report.passed);
+    assert_eq!(report.steps_run, 6);
+    assert_eq!(report.final_state, "Paid");
+    assert_eq!(
+        report.failure,
+        Some(Failure {
+            line: 9,
+            step: "pop".to_string(),
+            message: "can't pop_item while Paid".to_string(),
+            kind: FailureKind::IllegalAction {
+                state: "Paid".to_string(),
+            },
+        })
+    );
+
+    let output = stated(&["run", FAILING]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "{}: failed at line 9 (`pop`): can't pop_item while Paid\n",
+            FAILING
+        )
+    );
+}
+
+#[test]
+fn failing_scenarios_report_as_json() {
+    let output = stated(&["run", "--json", FAILING]);
+    assert_eq!(output.status.code(), Some(1));
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        report,
+        json!({
+            "passed": false,
+            "steps_run": 6,
+            "final_state": "Paid",
+            "final_cart": [],
+            "failure": {
+                "line": 9,
+                "step": "pop",
+                "message": "can't pop_item while Paid",
+                "reason": "illegal_action",
+                "state": "Paid",
+            },
+        })
+    );
+}
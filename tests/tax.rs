@@ -0,0 +1,209 @@
+use stated::online_shop::{
+    Coupon, Discount, MockGateway, Pricing, Product, PromotionEngine, Region, Shop, TaxConfigError,
+    TaxError, TaxPolicy, TaxRate, TaxRule, TaxTable,
+};
+
+mod common;
+use common::{checkout, eur, product, ready_to_pay};
+
+// The same rules the demo shop uses, read from disk.
+fn rules() -> TaxTable {
+    TaxTable::load(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/config/tax_rules.json"
+    ))
+    .unwrap()
+}
+
+#[test]
+fn demo_rules_come_from_the_config_file() {
+    let rules = rules();
+    assert_eq!(rules, TaxTable::demo());
+    assert_eq!(
+        rules.rule(&"NL".into(), &"food".into()),
+        Some(TaxRule {
+            region: "NL".into(),
+            category: "food".into(),
+            rate: TaxRate::from_basis_points(900),
+            pricing: Pricing::Inclusive,
+        })
+    );
+    assert_eq!(rules.rule(&"NL".into(), &"toys".into()), None);
+}
+
+#[test]
+fn inclusive_prices_already_contain_the_tax() {
+    let shop = Shop::new().with_taxes(rules());
+    let customer = checkout(
+        &shop,
+        "alice",
+        &[product("mug"), product("mug"), product("tea")],
+    );
+    let customer = customer.ship_to("NL".into()).ok().unwrap();
+
+    let breakdown = customer.tax_breakdown().unwrap();
+    let taxes: Vec<_> = breakdown
+        .lines
+        .iter()
+        .map(|line| (line.sku.as_str(), line.net, line.tax, line.gross))
+        .collect();
+    assert_eq!(
+        taxes,
+        [
+            // 24.00 / 1.21 = 19.834..., and 4.50 / 1.09 = 4.128...
+            ("mug", eur(1983), eur(417), eur(2400)),
+            ("tea", eur(413), eur(37), eur(450)),
+        ]
+    );
+    assert_eq!(
+        (breakdown.net, breakdown.tax, breakdown.gross),
+        (eur(2396), eur(454), eur(2850))
+    );
+    assert_eq!(customer.amount_due(), Ok(customer.total().unwrap()));
+}
+
+#[test]
+fn exclusive_prices_have_the_tax_added() {
+    let shop = Shop::new().with_taxes(rules());
+    let customer = checkout(&shop, "alice", &[product("lamp"), product("tea")]);
+    let customer = customer.ship_to("US-NY".into()).ok().unwrap();
+
+    let breakdown = customer.tax_breakdown().unwrap();
+    // 39.99 * 8.88% = 3.551..., and food isn't taxed.
+    assert_eq!(breakdown.tax, eur(355));
+    assert_eq!(customer.total(), Ok(eur(4449)));
+    assert_eq!(customer.amount_due(), Ok(eur(4804)));
+}
+
+#[test]
+fn the_region_has_to_be_known_and_taxable() {
+    let shop = Shop::new().with_taxes(rules());
+    let customer = checkout(&shop, "alice", &[product("mug")]);
+    assert_eq!(customer.tax_breakdown(), Err(TaxError::NoRegion));
+    assert_eq!(customer.amount_due(), Err(TaxError::NoRegion));
+
+    let (customer, err) = customer.ship_to("XX".into()).err().unwrap();
+    assert_eq!(
+        err,
+        TaxError::NoRule {
+            region: "XX".into(),
+            category: "standard".into(),
+        }
+    );
+    assert_eq!(customer.region(), None);
+
+    // The region sticks around while shopping, but something it has no rule
+    // for can still be added to the cart.
+    let customer = customer.ship_to("DE".into()).ok().unwrap();
+    let toy = Product::new("kite", "Kite", eur(1000)).with_tax_category("toys");
+    let customer = customer.cancel_checkout().add_item(toy);
+    assert_eq!(customer.region(), Some(&Region::from("DE")));
+    assert_eq!(
+        customer.proceed_to_checkout().ok().unwrap().tax_breakdown(),
+        Err(TaxError::NoRule {
+            region: "DE".into(),
+            category: "toys".into(),
+        })
+    );
+}
+
+#[test]
+fn coupons_come_off_before_tax() {
+    let shop = Shop::demo();
+    let customer = checkout(&shop, "alice", &[product("lamp")]);
+    let customer = customer.ship_to("US-NY".into()).ok().unwrap();
+    let customer = customer.apply_coupon("FIVEOFF").ok().unwrap();
+    // 39.99 - 5.00, plus 3.11 tax
+    assert_eq!(customer.amount_due(), Ok(eur(3810)));
+}
+
+#[test]
+fn discounts_are_spread_over_the_lines_before_tax() {
+    let promotions = PromotionEngine::default()
+        .with_coupon(Coupon::new("FIVEOFF", Discount::FixedOff(eur(500))));
+    let shop = Shop::new().with_promotions(promotions).with_taxes(rules());
+    let customer = checkout(
+        &shop,
+        "alice",
+        &[product("mug"), product("mug"), product("tea")],
+    );
+    let customer = customer.ship_to("NL".into()).ok().unwrap();
+    let customer = customer.apply_coupon("FIVEOFF").ok().unwrap();
+
+    let breakdown = customer.tax_breakdown().unwrap();
+    let taxes: Vec<_> = breakdown
+        .lines
+        .iter()
+        .map(|line| (line.sku.as_str(), line.discount, line.tax, line.gross))
+        .collect();
+    // In proportion to 24.00 and 4.50, that's 4.2105... and 0.7894... off, and
+    // the cent left over from rounding down goes to the tea.
+    assert_eq!(
+        taxes,
+        [
+            ("mug", eur(421), eur(343), eur(1979)),
+            ("tea", eur(79), eur(31), eur(371)),
+        ]
+    );
+    assert_eq!(breakdown.gross, customer.total().unwrap());
+    assert_eq!(customer.amount_due(), Ok(eur(2350)));
+}
+
+#[test]
+fn receipts_show_the_tax_on_what_was_paid() {
+    let promotions =
+        PromotionEngine::default().with_coupon(Coupon::new("HALF", Discount::PercentOff(50)));
+    let shop = Shop::new().with_promotions(promotions).with_taxes(rules());
+    let chair = Product::new("chair", "Desk chair", eur(12100));
+    let customer = checkout(&shop, "alice", &[chair]);
+    let customer = customer.apply_coupon("HALF").ok().unwrap();
+
+    let order = ready_to_pay(customer, "NL")
+        .finalise_payment(&MockGateway::new())
+        .ok()
+        .unwrap();
+    // 60.50 / 1.21 = 50.00
+    assert_eq!(order.total(), eur(6050));
+    assert_eq!(order.tax().tax, eur(1050));
+    let receipt = order.receipt().to_string();
+    assert!(
+        receipt.contains("\n  Tax (NL, included)                     10.50 EUR\n"),
+        "{}",
+        receipt
+    );
+}
+
+#[test]
+fn rates_are_written_as_percentages() {
+    for (rate, basis_points) in [("21%", 2100), ("5.5%", 550), ("8.88%", 888), ("0%", 0)] {
+        assert_eq!(rate.parse(), Ok(TaxRate::from_basis_points(basis_points)));
+        assert_eq!(TaxRate::from_basis_points(basis_points).to_string(), rate);
+    }
+    for invalid in ["21", "%", "5.555%", "-1%", "a%"] {
+        assert!(invalid.parse::<TaxRate>().is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn config_errors() {
+    let duplicate = r#"[
+        { "region": "NL", "category": "food", "rate": "9%", "pricing": "inclusive" },
+        { "region": "NL", "category": "food", "rate": "6%", "pricing": "inclusive" }
+    ]"#;
+    assert!(matches!(
+        TaxTable::from_json(duplicate),
+        Err(TaxConfigError::DuplicateRule { .. })
+    ));
+
+    let invalid_rate =
+        r#"[{ "region": "NL", "category": "food", "rate": "9", "pricing": "inclusive" }]"#;
+    assert!(matches!(
+        TaxTable::from_json(invalid_rate),
+        Err(TaxConfigError::Parse(_))
+    ));
+
+    assert!(matches!(
+        TaxTable::load("does/not/exist.json"),
+        Err(TaxConfigError::Io(_))
+    ));
+}